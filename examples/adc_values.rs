@@ -31,7 +31,12 @@ fn main() -> ! {
     ) {
         cortex_m::interrupt::free(move |cs| {
             let mut flash = dp.FLASH;
-            let mut rcc = dp.RCC.configure().sysclk(24.mhz()).freeze(&mut flash);
+            let mut rcc = dp
+                .RCC
+                .configure()
+                .sysclk(24.mhz())
+                .freeze(&mut flash)
+                .unwrap();
 
             let gpioa = dp.GPIOA.split(&mut rcc);
 
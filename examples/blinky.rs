@@ -12,7 +12,12 @@ use cortex_m_rt::entry;
 #[entry]
 fn main() -> ! {
     if let Some(mut p) = pac::Peripherals::take() {
-        let mut rcc = p.RCC.configure().sysclk(8.mhz()).freeze(&mut p.FLASH);
+        let mut rcc = p
+            .RCC
+            .configure()
+            .sysclk(8.mhz())
+            .freeze(&mut p.FLASH)
+            .unwrap();
 
         let gpioa = p.GPIOA.split(&mut rcc);
 
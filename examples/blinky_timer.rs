@@ -12,7 +12,12 @@ use cortex_m_rt::entry;
 #[entry]
 fn main() -> ! {
     if let Some(mut p) = pac::Peripherals::take() {
-        let mut rcc = p.RCC.configure().sysclk(8.mhz()).freeze(&mut p.FLASH);
+        let mut rcc = p
+            .RCC
+            .configure()
+            .sysclk(8.mhz())
+            .freeze(&mut p.FLASH)
+            .unwrap();
 
         let gpioa = p.GPIOA.split(&mut rcc);
 
@@ -20,7 +25,7 @@ fn main() -> ! {
         let mut led = cortex_m::interrupt::free(move |cs| gpioa.pa5.into_push_pull_output(cs));
 
         // Set up a timer expiring after 1s
-        let mut timer = Timer::tim1(p.TIM1, Hertz(5), &mut rcc);
+        let mut timer = Timer::tim1(p.TIM1, Hertz(5), &mut rcc).unwrap();
 
         loop {
             led.toggle().ok();
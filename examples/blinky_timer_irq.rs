@@ -62,7 +62,8 @@ fn main() -> ! {
                 .configure()
                 .sysclk(24.mhz())
                 .pclk(24.mhz())
-                .freeze(&mut p.FLASH);
+                .freeze(&mut p.FLASH)
+                .unwrap();
 
             let gpioa = p.GPIOA.split(&mut rcc);
 
@@ -73,7 +74,7 @@ fn main() -> ! {
             *GLED.borrow(cs).borrow_mut() = Some(led);
 
             // Set up a timer expiring after 1s
-            let mut timer = Timer::tim16(p.TIM16, Hertz(10), &mut rcc);
+            let mut timer = Timer::tim16(p.TIM16, Hertz(10), &mut rcc).unwrap();
 
             // Generate an interrupt when the timer expires
             timer.listen(Event::TimeOut);
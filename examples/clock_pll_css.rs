@@ -0,0 +1,52 @@
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    delay::Delay,
+    pac,
+    prelude::*,
+    rcc::{self, HSEBypassMode, PLLSrc},
+};
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::{entry, exception};
+
+#[entry]
+fn main() -> ! {
+    if let (Some(mut p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
+        // Run at 48 MHz from a 24 MHz crystal doubled by the PLL and watch the crystal
+        let mut rcc = p
+            .RCC
+            .configure()
+            .pll(PLLSrc::HSE(24.mhz().into(), HSEBypassMode::NotBypassed))
+            .css(true)
+            .freeze(&mut p.FLASH)
+            .unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+        let mut led = cortex_m::interrupt::free(move |cs| gpioa.pa5.into_push_pull_output(cs));
+
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        loop {
+            // Blinks slower once the crystal failed and the core runs from the HSI
+            led.toggle().ok();
+            delay.delay_ms(500_u16);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
+
+#[exception]
+fn NonMaskableInt() {
+    // Keep running from HSISYS if the crystal failed
+    rcc::handle_css_nmi();
+}
@@ -0,0 +1,68 @@
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    delay::Delay,
+    pac,
+    prelude::*,
+    rcc::{Config, HSIFreq},
+    serial::Serial,
+};
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let (Some(p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
+        let mut flash = p.FLASH;
+
+        // Full speed through the PLL while busy, 4 MHz straight from the HSI while idle
+        let fast = Config::default().hsi(HSIFreq::Freq24mhz).sysclk(48.mhz());
+        let slow = Config::default().hsi(HSIFreq::Freq4mhz);
+
+        let mut rcc = p
+            .RCC
+            .configure()
+            .hsi(HSIFreq::Freq24mhz)
+            .sysclk(48.mhz())
+            .freeze(&mut flash)
+            .unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+
+        let (tx, rx) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa2.into_alternate_af1(cs),
+                gpioa.pa3.into_alternate_af1(cs),
+            )
+        });
+
+        let mut serial = Serial::usart1(p.USART1, (tx, rx), 9_600.bps(), &mut rcc);
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        loop {
+            rcc.reconfigure(slow, &mut flash).unwrap();
+            serial.update_clocks(&rcc.clocks);
+            delay.update_clocks(&rcc.clocks);
+            writeln!(serial, "idle at {} Hz", rcc.clocks.sysclk().0).ok();
+            delay.delay_ms(1_000_u16);
+
+            rcc.reconfigure(fast, &mut flash).unwrap();
+            serial.update_clocks(&rcc.clocks);
+            delay.update_clocks(&rcc.clocks);
+            writeln!(serial, "busy at {} Hz", rcc.clocks.sysclk().0).ok();
+            delay.delay_ms(1_000_u16);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,54 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{delay::Delay, pac, prelude::*};
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+/* Reads the response bits of a DHT22 sensor with its data line on PA4 */
+
+#[entry]
+fn main() -> ! {
+    if let (Some(mut p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
+        let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+        let mut data = gpioa.pa4.into_dynamic();
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        loop {
+            // Pull the line low for at least 1 ms to request a measurement
+            data.make_open_drain_output();
+            data.set_low().ok();
+            delay.delay_ms(2_u16);
+            data.set_high().ok();
+
+            // Release the line and let the sensor drive it
+            data.make_pull_up_input();
+            let mut bits = 0_u64;
+            for _ in 0..41 {
+                while data.is_low().unwrap_or(false) {}
+                delay.delay_us(35_u16);
+                bits = (bits << 1) | u64::from(data.is_high().unwrap_or(false));
+                while data.is_high().unwrap_or(false) {}
+            }
+            // Tenths of %RH and of °C, followed by a checksum byte
+            let _humidity = (bits >> 24) as u16;
+            let _temperature = (bits >> 8) as u16;
+
+            // Writing while in input mode is rejected
+            assert!(data.set_high().is_err());
+
+            delay.delay_ms(2_000_u16);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
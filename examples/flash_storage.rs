@@ -0,0 +1,62 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    flash::{Flash, PAGE_SIZE},
+    pac,
+    prelude::*,
+    serial::Serial,
+};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut p) = pac::Peripherals::take() {
+        let mut rcc = p
+            .RCC
+            .configure()
+            .sysclk(24.mhz())
+            .freeze(&mut p.FLASH)
+            .unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+
+        // Configure serial TX pin
+        let tx = cortex_m::interrupt::free(move |cs| gpioa.pa2.into_alternate_af1(cs));
+
+        // Obtain a serial peripheral with for unidirectional communication
+        let mut serial = Serial::usart1tx(p.USART1, tx, 115_200.bps(), &mut rcc);
+
+        let mut flash = Flash::new(p.FLASH);
+
+        // Use the last page as storage for a boot counter
+        let offset = (flash.capacity() - PAGE_SIZE) as u32;
+
+        let mut page = [0xFF; PAGE_SIZE];
+        flash.read(offset, &mut page).ok();
+
+        let count = match u32::from_le_bytes([page[0], page[1], page[2], page[3]]) {
+            0xFFFF_FFFF => 0,
+            n => n,
+        } + 1;
+        page[..4].copy_from_slice(&count.to_le_bytes());
+
+        flash.erase(offset, offset + PAGE_SIZE as u32).ok();
+        match flash.write(offset, &page) {
+            Ok(()) => writeln!(serial, "Boot count: {}\r", count).ok(),
+            Err(e) => writeln!(serial, "Flash error: {:?}\r", e).ok(),
+        };
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -23,7 +23,12 @@ static GPIO: Mutex<RefCell<Option<LEDPIN>>> = Mutex::new(RefCell::new(None));
 fn main() -> ! {
     if let (Some(mut p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
         cortex_m::interrupt::free(move |cs| {
-            let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut p.FLASH);
+            let mut rcc = p
+                .RCC
+                .configure()
+                .sysclk(24.mhz())
+                .freeze(&mut p.FLASH)
+                .unwrap();
 
             // Get access to individual pins in the GPIO port
             let gpioa = p.GPIOA.split(&mut rcc);
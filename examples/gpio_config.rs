@@ -0,0 +1,51 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    gpio::{Pull, Speed},
+    i2c::I2c,
+    pac,
+    prelude::*,
+};
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut p) = pac::Peripherals::take() {
+        let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+
+        let (mut scl, mut sda, mut enable) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa3.into_alternate_af12(cs).set_open_drain(cs),
+                gpioa.pa2.into_alternate_af12(cs).set_open_drain(cs),
+                gpioa.pa5.into_push_pull_output(cs),
+            )
+        });
+
+        // Weak internal pull ups are enough for short buses, slow edges reduce ringing
+        scl.set_pull(Pull::Up);
+        sda.set_pull(Pull::Up);
+        scl.set_speed(Speed::Low);
+        sda.set_speed(Speed::Low);
+
+        // Keep the power stage off, the pin can't be reconfigured by accident afterwards
+        enable.set_low().ok();
+        // Fails if another pin of GPIOA was locked before
+        let mut enable = enable.lock().ok().unwrap();
+
+        let _i2c = I2c::i2c(p.I2C, (scl, sda), 100.khz(), &mut rcc);
+
+        enable.set_high().ok();
+    }
+
+    loop {
+        continue;
+    }
+}
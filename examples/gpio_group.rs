@@ -0,0 +1,46 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{delay::Delay, gpio::PinGroup, pac, prelude::*};
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+/* Drives a 4 bit parallel bus on PA4..PA7, e.g. the data lines of a character LCD */
+
+#[entry]
+fn main() -> ! {
+    if let (Some(mut p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
+        let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+        let pins = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa4.into_push_pull_output(cs),
+                gpioa.pa5.into_push_pull_output(cs),
+                gpioa.pa6.into_push_pull_output(cs),
+                gpioa.pa7.into_push_pull_output(cs),
+            )
+        });
+
+        // Mixing in a pin of GPIOB would not compile
+        let mut bus = PinGroup::new(pins);
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        let mut nibble = 0_u16;
+        loop {
+            // All four lines change with the same write
+            bus.write(nibble << 4);
+            nibble = (nibble + 1) & 0xF;
+            delay.delay_ms(100_u16);
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
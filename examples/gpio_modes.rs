@@ -0,0 +1,45 @@
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    delay::Delay,
+    gpio::{gpioa::PA4, Input, Output, PullUp, PushPull},
+    pac,
+    prelude::*,
+};
+
+use cortex_m::peripheral::Peripherals;
+use cortex_m_rt::entry;
+
+/* Shares PA4 between an LED and a push button to ground */
+
+#[entry]
+fn main() -> ! {
+    if let (Some(mut p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
+        let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+
+        // No critical section has to be passed in, interrupts are masked only during the conversion
+        let mut led: PA4<Output<PushPull>> = gpioa.pa4.into_mode();
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        loop {
+            led.toggle().ok();
+
+            // Briefly sample the button, the pin is an output again afterwards
+            let pressed =
+                led.with_input(|pin: &mut PA4<Input<PullUp>>| pin.is_low().unwrap_or(false));
+
+            delay.delay_ms(if pressed { 100_u16 } else { 500_u16 });
+        }
+    }
+
+    loop {
+        continue;
+    }
+}
@@ -0,0 +1,48 @@
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    pac,
+    prelude::*,
+    rcc::{HSIFreq, LSEDrive, LSEMode},
+    serial::Serial,
+};
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut p) = pac::Peripherals::take() {
+        let mut rcc = p
+            .RCC
+            .configure()
+            .hsi(HSIFreq::Freq22_12mhz)
+            .freeze(&mut p.FLASH)
+            .unwrap();
+
+        let factory = rcc.hsi_trim();
+        // Tune the HSI against a 32.768 kHz watch crystal for accurate baud rates
+        let trim = rcc.auto_trim_hsi_lse(&mut p.TIM14, LSEMode::Crystal(LSEDrive::Medium));
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+        let (tx, rx) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa2.into_alternate_af1(cs),
+                gpioa.pa3.into_alternate_af1(cs),
+            )
+        });
+        let mut serial = Serial::usart1(p.USART1, (tx, rx), 115_200.bps(), &mut rcc);
+
+        writeln!(serial, "HSI trim {} -> {}", factory, trim).ok();
+    }
+
+    loop {
+        continue;
+    }
+}
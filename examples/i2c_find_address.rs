@@ -24,7 +24,8 @@ fn main() -> ! {
                 .configure()
                 .sysclk(24.mhz())
                 .pclk(24.mhz())
-                .freeze(&mut flash);
+                .freeze(&mut flash)
+                .unwrap();
 
             let gpioa = p.GPIOA.split(&mut rcc);
 
@@ -8,7 +8,7 @@ use py32f0xx_hal as hal;
 use crate::hal::{
     delay::Delay,
     gpio::*,
-    pac::{interrupt, Interrupt, Peripherals, EXTI},
+    pac::{interrupt, Interrupt, Peripherals, EXTI, SYSCFG},
     prelude::*,
 };
 
@@ -30,12 +30,16 @@ static INT: Mutex<RefCell<Option<EXTI>>> = Mutex::new(RefCell::new(None));
 fn main() -> ! {
     if let (Some(p), Some(cp)) = (Peripherals::take(), c_m_Peripherals::take()) {
         cortex_m::interrupt::free(move |cs| {
-            // Enable clock for SYSCFG
-            let rcc = p.RCC;
-            rcc.apbenr2.modify(|_, w| w.syscfgen().set_bit());
-
             let mut flash = p.FLASH;
-            let mut rcc = rcc.configure().sysclk(8.mhz()).freeze(&mut flash);
+            let mut rcc = p
+                .RCC
+                .configure()
+                .sysclk(8.mhz())
+                .freeze(&mut flash)
+                .unwrap();
+
+            // Enable clock for SYSCFG
+            SYSCFG::enable(&mut rcc);
 
             let gpioa = p.GPIOA.split(&mut rcc);
             let gpiob = p.GPIOB.split(&mut rcc);
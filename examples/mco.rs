@@ -0,0 +1,32 @@
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use py32f0xx_hal as hal;
+
+use crate::hal::{
+    pac,
+    prelude::*,
+    rcc::{MCODiv, MCOSrc, Mco},
+};
+
+use cortex_m_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut p) = pac::Peripherals::take() {
+        let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+
+        let gpioa = p.GPIOA.split(&mut rcc);
+        let pin = cortex_m::interrupt::free(move |cs| gpioa.pa1.into_alternate_af15(cs));
+
+        // Output the LSI on PA1 to measure it with a scope, it is started if needed
+        let _mco = Mco::new(pin, MCOSrc::Lsi, MCODiv::NotDivided, &mut rcc);
+    }
+
+    loop {
+        continue;
+    }
+}
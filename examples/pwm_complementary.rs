@@ -16,7 +16,12 @@ use hal::{delay::Delay, pac, prelude::*, pwm};
 fn main() -> ! {
     if let Some(mut dp) = pac::Peripherals::take() {
         // Set up the system clock.
-        let mut rcc = dp.RCC.configure().sysclk(8.mhz()).freeze(&mut dp.FLASH);
+        let mut rcc = dp
+            .RCC
+            .configure()
+            .sysclk(8.mhz())
+            .freeze(&mut dp.FLASH)
+            .unwrap();
 
         let gpioa = dp.GPIOA.split(&mut rcc);
         let channels = cortex_m::interrupt::free(move |cs| {
@@ -26,7 +31,7 @@ fn main() -> ! {
             )
         });
 
-        let pwm = pwm::tim1(dp.TIM1, channels, &mut rcc, 20u32.khz());
+        let pwm = pwm::tim1(dp.TIM1, channels, &mut rcc, 20u32.khz()).unwrap();
         let (mut ch1, mut ch1n) = pwm;
         let max_duty = ch1.get_max_duty();
         ch1.set_duty(max_duty / 2);
@@ -0,0 +1,67 @@
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+// Halt on panic
+use panic_halt as _;
+
+use cortex_m_rt::entry;
+
+use py32f0xx_hal as hal;
+
+use hal::{
+    pac,
+    prelude::*,
+    pwm::{BreakPolarity, LockLevel, Pwm},
+};
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut dp) = pac::Peripherals::take() {
+        // Set up the system clock.
+        let mut rcc = dp
+            .RCC
+            .configure()
+            .sysclk(24.mhz())
+            .freeze(&mut dp.FLASH)
+            .unwrap();
+
+        let gpioa = dp.GPIOA.split(&mut rcc);
+        let (channels, bkin) = cortex_m::interrupt::free(move |cs| {
+            (
+                (
+                    gpioa.pa8.into_alternate_af2(cs), // on TIM1_CH1
+                    gpioa.pa7.into_alternate_af2(cs), // on TIM1_CH1N
+                ),
+                gpioa.pa6.into_alternate_af2(cs), // on TIM1_BKIN
+            )
+        });
+
+        let (mut pwm, (mut ch1, mut ch1n)) =
+            Pwm::tim1(dp.TIM1, channels, &mut rcc, 20u32.khz()).unwrap();
+
+        // Keep both transistors off for 500ns around each switching edge
+        pwm.set_dead_time(500);
+        // Shut down the outputs when the driver pulls its fault line low
+        pwm.enable_break(&bkin, BreakPolarity::ActiveLow);
+        pwm.set_automatic_output(false);
+        pwm.set_lock_level(LockLevel::Level1);
+
+        let max_duty = ch1.get_max_duty();
+        ch1.set_duty(max_duty / 2);
+        ch1.enable();
+        ch1n.enable();
+
+        loop {
+            if pwm.is_break_pending() {
+                // Outputs stay off until the fault is cleared
+                pwm.clear_break();
+            }
+        }
+    }
+
+    // something went wrong when acquiring peripheral access
+    loop {
+        cortex_m::asm::nop();
+    }
+}
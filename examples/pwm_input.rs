@@ -0,0 +1,63 @@
+#![no_main]
+#![no_std]
+
+// Halt on panic
+use panic_halt as _;
+
+use cortex_m_rt::entry;
+
+use py32f0xx_hal as hal;
+
+use hal::{capture::PwmInput, pac, prelude::*, pwm, serial::Serial};
+
+use core::fmt::Write;
+
+#[entry]
+fn main() -> ! {
+    if let Some(mut dp) = pac::Peripherals::take() {
+        // Set up the system clock.
+        let mut rcc = dp
+            .RCC
+            .configure()
+            .sysclk(24.mhz())
+            .freeze(&mut dp.FLASH)
+            .unwrap();
+
+        let gpioa = dp.GPIOA.split(&mut rcc);
+        let (tx, pwm_pin, input_pin) = cortex_m::interrupt::free(move |cs| {
+            (
+                gpioa.pa2.into_alternate_af1(cs), // on USART1_TX
+                gpioa.pa8.into_alternate_af2(cs), // on TIM1_CH1
+                gpioa.pa6.into_alternate_af1(cs), // on TIM3_CH1
+            )
+        });
+
+        let mut serial = Serial::usart1tx(dp.USART1, tx, 115_200.bps(), &mut rcc);
+
+        // Generate a 1kHz signal with 25% duty cycle, connect PA8 to PA6 to measure it
+        let mut ch1 = pwm::tim1(dp.TIM1, pwm_pin, &mut rcc, 1u32.khz()).unwrap();
+        ch1.set_duty(ch1.get_max_duty() / 4);
+        ch1.enable();
+
+        let mut input = PwmInput::tim3(dp.TIM3, input_pin, &mut rcc, 1u32.mhz(), 0).unwrap();
+
+        loop {
+            if input.is_updated() {
+                if let (Some(freq), Some((high, period))) = (input.frequency(), input.duty_cycle())
+                {
+                    writeln!(
+                        serial,
+                        "{} Hz, high for {}/{} ticks\r",
+                        freq.0, high, period
+                    )
+                    .ok();
+                }
+            }
+            cortex_m::asm::delay(24_000_000);
+        }
+    }
+
+    loop {
+        cortex_m::asm::nop();
+    }
+}
@@ -0,0 +1,51 @@
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+// Halt on panic
+use panic_halt as _;
+
+use cortex_m_rt::entry;
+
+use py32f0xx_hal as hal;
+
+use hal::{delay::Delay, pac, prelude::*, pwm::Pwm};
+
+#[entry]
+fn main() -> ! {
+    if let (Some(mut dp), Some(cp)) = (pac::Peripherals::take(), cortex_m::Peripherals::take()) {
+        // Set up the system clock.
+        let mut rcc = dp
+            .RCC
+            .configure()
+            .sysclk(24.mhz())
+            .freeze(&mut dp.FLASH)
+            .unwrap();
+
+        let gpioa = dp.GPIOA.split(&mut rcc);
+        let buzzer = cortex_m::interrupt::free(move |cs| {
+            gpioa.pa6.into_alternate_af1(cs) // on TIM3_CH1
+        });
+
+        let (mut pwm, mut ch1) = Pwm::tim3(dp.TIM3, buzzer, &mut rcc, 440u32.hz()).unwrap();
+        ch1.enable();
+
+        let mut delay = Delay::new(cp.SYST, &rcc);
+
+        // C major scale
+        let notes = [262u32, 294, 330, 349, 392, 440, 494, 523];
+
+        loop {
+            for note in notes.iter() {
+                pwm.set_frequency(note.hz()).unwrap();
+                ch1.set_duty(pwm.get_max_duty() / 2);
+                delay.delay_ms(250u16);
+            }
+        }
+    }
+
+    // something went wrong when acquiring peripheral access
+    loop {
+        cortex_m::asm::nop();
+    }
+}
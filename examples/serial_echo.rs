@@ -25,7 +25,8 @@ fn main() -> ! {
             .configure()
             .hsi(HSIFreq::Freq24mhz)
             .sysclk(24.mhz())
-            .freeze(&mut flash);
+            .freeze(&mut flash)
+            .unwrap();
 
         rcc.configure_mco(MCOSrc::Sysclk, MCODiv::NotDivided);
 
@@ -59,7 +59,12 @@ fn main() -> ! {
     if let (Some(p), Some(cp)) = (Peripherals::take(), c_m_Peripherals::take()) {
         let mut serial = cortex_m::interrupt::free(move |cs| {
             let mut flash = p.FLASH;
-            let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut flash);
+            let mut rcc = p
+                .RCC
+                .configure()
+                .sysclk(24.mhz())
+                .freeze(&mut flash)
+                .unwrap();
 
             // Use USART1 with PA2 and PA3 as serial port
             let gpioa = p.GPIOA.split(&mut rcc);
@@ -67,7 +72,7 @@ fn main() -> ! {
             let rx = gpioa.pa3.into_alternate_af1(cs);
 
             // Set up a timer expiring every millisecond
-            let mut timer = Timer::tim16(p.TIM16, 1000.hz(), &mut rcc);
+            let mut timer = Timer::tim16(p.TIM16, 1000.hz(), &mut rcc).unwrap();
 
             // Generate an interrupt when the timer expires
             timer.listen(Event::TimeOut);
@@ -23,7 +23,7 @@ fn main() -> ! {
 
     if let Some(p) = pac::Peripherals::take() {
         let mut flash = p.FLASH;
-        let mut rcc = p.RCC.configure().freeze(&mut flash);
+        let mut rcc = p.RCC.configure().freeze(&mut flash).unwrap();
 
         let gpioa = p.GPIOA.split(&mut rcc);
 
@@ -45,7 +45,7 @@ fn main() -> ! {
         cortex_m::peripheral::Peripherals::take(),
     ) {
         let mut flash = p.FLASH;
-        let mut rcc = p.RCC.configure().freeze(&mut flash);
+        let mut rcc = p.RCC.configure().freeze(&mut flash).unwrap();
 
         let mut delay = Delay::new(cp.SYST, &rcc);
 
@@ -77,7 +77,7 @@ fn main() -> ! {
         info!("MFRC522 version: 0x{:02x}", ver);
         assert!(ver == 0x91 || ver == 0x92);
 
-        let mut timer = Timer::tim1(p.TIM1, Hertz(1), &mut rcc);
+        let mut timer = Timer::tim1(p.TIM1, Hertz(1), &mut rcc).unwrap();
 
         loop {
             info!("Waiting for card...");
@@ -16,7 +16,12 @@ use core::fmt::Write;
 fn main() -> ! {
     if let (Some(p), Some(cp)) = (pac::Peripherals::take(), Peripherals::take()) {
         let mut flash = p.FLASH;
-        let mut rcc = p.RCC.configure().sysclk(8.mhz()).freeze(&mut flash);
+        let mut rcc = p
+            .RCC
+            .configure()
+            .sysclk(8.mhz())
+            .freeze(&mut flash)
+            .unwrap();
 
         let gpioa = p.GPIOA.split(&mut rcc);
         let dbg = p.DBG;
@@ -14,7 +14,7 @@
 //!
 //! cortex_m::interrupt::free(|cs| {
 //!     let mut p = pac::Peripherals::take().unwrap();
-//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH);
+//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
 //!
 //!     let gpioa = p.GPIOA.split(&mut rcc);
 //!
@@ -63,7 +63,7 @@ use crate::{
         },
         ADC,
     },
-    rcc::Rcc,
+    rcc::{Enable, Rcc, Reset},
 };
 
 /// Analog to Digital converter interface
@@ -524,9 +524,8 @@ impl Adc {
     }
 
     fn select_clock(&mut self, rcc: &mut Rcc, ckmode: AdcClockMode) {
-        rcc.regs.apbrstr2.modify(|_, w| w.adcrst().reset());
-        rcc.regs.apbrstr2.modify(|_, w| w.adcrst().clear_bit());
-        rcc.regs.apbenr2.modify(|_, w| w.adcen().enabled());
+        ADC::enable(rcc);
+        ADC::reset(rcc);
         self.rb.cfgr2.modify(|_, w| w.ckmode().variant(ckmode.into()));
     }
 
@@ -0,0 +1,536 @@
+//! API for timer input capture
+//!
+//! `InputCapture` latches the counter value on edges of up to four input pins,
+//! `PwmInput` uses two channels of TIM1/TIM3 on a single pin together with the
+//! slave mode controller to measure the period and duty cycle of a PWM signal.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::capture::PwmInput;
+//!
+//! cortex_m::interrupt::free(|cs| {
+//!     let mut p = pac::Peripherals::take().unwrap();
+//!     let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut p.FLASH).unwrap();
+//!
+//!     let gpioa = p.GPIOA.split(&mut rcc);
+//!     let pin = gpioa.pa6.into_alternate_af1(cs);
+//!
+//!     // Count with 1MHz, signals down to ~16Hz can be measured
+//!     let pwm_input = PwmInput::tim3(p.TIM3, pin, &mut rcc, 1.mhz(), 0).unwrap();
+//!
+//!     if let Some(freq) = pwm_input.frequency() {
+//!         // ...
+//!     }
+//! });
+//! ```
+
+use crate::pwm::Pins;
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::time::Hertz;
+use crate::timers::{self, compute_psc, PinC1};
+
+use embedded_hal as hal;
+use embedded_hal::Capture;
+
+/// Capture channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    C1,
+    C2,
+    C3,
+    C4,
+}
+
+/// Edge(s) of the input signal latching the counter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    Rising,
+    Falling,
+    Both,
+}
+
+/// Number of edges between two captures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prescaler {
+    /// Capture on every edge
+    Div1 = 0,
+    /// Capture on every 2nd edge
+    Div2 = 1,
+    /// Capture on every 4th edge
+    Div4 = 2,
+    /// Capture on every 8th edge
+    Div8 = 3,
+}
+
+/// Input capture errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A capture was overwritten before it was read
+    Overcapture,
+}
+
+/// Capture channel configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    polarity: Polarity,
+    prescaler: Prescaler,
+    filter: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            polarity: Polarity::Rising,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+impl Config {
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Digital input filter, `0` disables it (see the `ICxF` field of the reference manual)
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter & 0xF;
+        self
+    }
+}
+
+/// Input capture on the channels of a timer
+pub struct InputCapture<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    tclk: Hertz,
+    clk: Hertz,
+}
+
+/// PWM input measurement on channel 1 of a timer
+pub struct PwmInput<TIM, PIN> {
+    tim: TIM,
+    pin: PIN,
+    clk: Hertz,
+}
+
+/// Frequency of a counter clocked by `tclk` through the prescaler `psc`
+fn counter_clk(tclk: Hertz, psc: u16) -> Hertz {
+    Hertz(tclk.0 / (u32::from(psc) + 1))
+}
+
+macro_rules! capture {
+    ($($TIM:ident: $tim:ident, [
+        $($C:ident: ($ccmr:ident, $ccXs:ident, $icXf:ident, $icXpsc:ident, $ccXe:ident, $ccXp:ident, $ccXnp:ident, $ccXif:ident, $ccXof:ident, $ccXie:ident, $ccrX:ident),)+
+    ],)+) => {
+        $(
+            use crate::pac::$TIM;
+
+            impl<PINS> InputCapture<$TIM, PINS> {
+                /// Configures a TIM peripheral for input capture on the channels of `pins`
+                ///
+                /// The counter runs freely with the frequency `resolution`, which has to be
+                /// between the timer clock divided by 65536 and the timer clock. All channels
+                /// start capturing rising edges.
+                pub fn $tim<P, T>(
+                    tim: $TIM,
+                    pins: PINS,
+                    rcc: &mut Rcc,
+                    resolution: T,
+                ) -> Result<Self, timers::Error>
+                where
+                    PINS: Pins<$TIM, P>,
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk();
+                    let psc = compute_psc(tclk, resolution.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    let mut capture = InputCapture {
+                        tim,
+                        pins,
+                        tclk,
+                        clk: tclk,
+                    };
+                    capture.set_psc(psc);
+
+                    $(
+                        if PINS::$C {
+                            capture.configure(Channel::$C, Config::default());
+                        }
+                    )+
+
+                    capture.tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+                    capture.tim.cr1.modify(|_, w| w.cen().set_bit());
+                    Ok(capture)
+                }
+
+                /// Sets the frequency of the counter
+                ///
+                /// The achieved frequency is rounded up and can be read with `clock`.
+                /// Unlike `set_resolution`, a frequency out of range is reported instead of
+                /// being clamped.
+                pub fn try_set_resolution<R>(&mut self, resolution: R) -> Result<(), timers::Error>
+                where
+                    R: Into<Hertz>,
+                {
+                    let psc = compute_psc(self.tclk, resolution.into())?;
+                    self.set_psc(psc);
+                    Ok(())
+                }
+
+                fn set_psc(&mut self, psc: u16) {
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    // Load the prescaler
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.clk = counter_clk(self.tclk, psc);
+                }
+
+                /// Changes the edge, prescaler and filter of a channel
+                pub fn configure(&mut self, channel: Channel, config: Config) {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(
+                            Channel::$C => {
+                                // The channel has to be disabled to change its direction
+                                self.tim.ccer.modify(|_, w| w.$ccXe().clear_bit());
+                                self.tim.$ccmr().modify(|_, w| unsafe {
+                                    w.$ccXs()
+                                        .bits(0b01)
+                                        .$icXpsc()
+                                        .bits(config.prescaler as u8)
+                                        .$icXf()
+                                        .bits(config.filter)
+                                });
+                                let (p, np) = match config.polarity {
+                                    Polarity::Rising => (false, false),
+                                    Polarity::Falling => (true, false),
+                                    Polarity::Both => (true, true),
+                                };
+                                self.tim.ccer.modify(|_, w| {
+                                    w.$ccXp().bit(p).$ccXnp().bit(np).$ccXe().set_bit()
+                                });
+                            }
+                        )+
+                        _ => {}
+                    }
+                }
+
+                /// Starts listening for captures on `channel`
+                pub fn listen(&mut self, channel: Channel) {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$C => self.tim.dier.modify(|_, w| w.$ccXie().set_bit()),)+
+                        _ => {}
+                    }
+                }
+
+                /// Stops listening for captures on `channel`
+                pub fn unlisten(&mut self, channel: Channel) {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$C => self.tim.dier.modify(|_, w| w.$ccXie().clear_bit()),)+
+                        _ => {}
+                    }
+                }
+
+                /// Returns the frequency the counter runs with
+                pub fn clock(&self) -> Hertz {
+                    self.clk
+                }
+
+                /// Measures the frequency of the signal on `channel` from two consecutive captures
+                ///
+                /// Blocks until both captures happened. The period of the signal,
+                /// multiplied with the channel prescaler, must not exceed 65536 counter ticks.
+                pub fn measure_frequency(&mut self, channel: Channel) -> Result<Hertz, Error> {
+                    #[allow(unreachable_patterns)]
+                    let edges = match channel {
+                        $(Channel::$C => 1 << self.tim.$ccmr().read().$icXpsc().bits(),)+
+                        _ => 1,
+                    };
+
+                    // Discard a stale capture
+                    self.capture(channel).ok();
+                    let first = nb::block!(self.capture(channel))?;
+                    let second = nb::block!(self.capture(channel))?;
+
+                    let ticks = u32::from(second.wrapping_sub(first));
+                    let ticks = if ticks == 0 { 0x1_0000 } else { ticks };
+                    Ok(Hertz(self.clk.0 * edges / ticks))
+                }
+
+                /// Disables the timer and releases the TIM peripheral and the pins
+                pub fn release(self) -> ($TIM, PINS) {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    (self.tim, self.pins)
+                }
+            }
+
+            impl<PINS> hal::Capture for InputCapture<$TIM, PINS> {
+                type Error = Error;
+                type Channel = Channel;
+                type Time = Hertz;
+                type Capture = u16;
+
+                /// Returns the counter value latched by the last edge
+                ///
+                /// Channels without a pin never capture and always return `WouldBlock`.
+                fn capture(&mut self, channel: Channel) -> nb::Result<u16, Error> {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(
+                            Channel::$C => {
+                                let sr = self.tim.sr.read();
+                                if sr.$ccXof().bit_is_set() {
+                                    self.tim.sr.modify(|_, w| w.$ccXof().clear_bit());
+                                    // Reading the capture clears the pending flag
+                                    self.tim.$ccrX.read();
+                                    Err(nb::Error::Other(Error::Overcapture))
+                                } else if sr.$ccXif().bit_is_set() {
+                                    Ok(self.tim.$ccrX.read().ccr().bits() as u16)
+                                } else {
+                                    Err(nb::Error::WouldBlock)
+                                }
+                            }
+                        )+
+                        _ => Err(nb::Error::WouldBlock),
+                    }
+                }
+
+                fn disable(&mut self, channel: Channel) {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$C => self.tim.ccer.modify(|_, w| w.$ccXe().clear_bit()),)+
+                        _ => {}
+                    }
+                }
+
+                fn enable(&mut self, channel: Channel) {
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$C => self.tim.ccer.modify(|_, w| w.$ccXe().set_bit()),)+
+                        _ => {}
+                    }
+                }
+
+                fn get_resolution(&self) -> Hertz {
+                    self.clk
+                }
+
+                /// Sets the frequency of the counter
+                ///
+                /// The achieved frequency is rounded up and can be read with `clock`.
+                /// A frequency out of range is clamped to the slowest or fastest counter
+                /// frequency, use `try_set_resolution` to detect it.
+                fn set_resolution<R>(&mut self, resolution: R)
+                where
+                    R: Into<Hertz>,
+                {
+                    let tclk = self.tclk;
+                    let resolution = resolution.into();
+                    let psc = match compute_psc(tclk, resolution) {
+                        Ok(psc) => psc,
+                        Err(_) if resolution.0 > tclk.0 => 0,
+                        Err(_) => u16::MAX,
+                    };
+                    self.set_psc(psc);
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! pwm_input {
+    ($($TIM:ident: $tim:ident,)+) => {
+        $(
+            impl<PIN> PwmInput<$TIM, PIN>
+            where
+                PIN: PinC1<$TIM>,
+            {
+                /// Configures a TIM peripheral to measure the PWM signal on the channel 1 `pin`
+                ///
+                /// The counter runs with the frequency `resolution` and is reset on each
+                /// rising edge, channel 1 captures the period and channel 2 the high time.
+                /// `filter` sets the digital input filter, `0` disables it. `resolution` has to
+                /// be between the timer clock divided by 65536 and the timer clock.
+                pub fn $tim<T>(
+                    tim: $TIM,
+                    pin: PIN,
+                    rcc: &mut Rcc,
+                    resolution: T,
+                    filter: u8,
+                ) -> Result<Self, timers::Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk().0;
+                    let psc = compute_psc(Hertz(tclk), resolution.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+
+                    // Both channels capture TI1, channel 1 on the rising, channel 2 on the falling edge
+                    let filter = filter & 0xF;
+                    tim.ccmr1_input().write(|w| unsafe {
+                        w.cc1s()
+                            .bits(0b01)
+                            .ic1f()
+                            .bits(filter)
+                            .cc2s()
+                            .bits(0b10)
+                            .ic2f()
+                            .bits(filter)
+                    });
+                    tim.ccer.write(|w| {
+                        w.cc1p()
+                            .clear_bit()
+                            .cc1e()
+                            .set_bit()
+                            .cc2p()
+                            .set_bit()
+                            .cc2e()
+                            .set_bit()
+                    });
+
+                    // Reset the counter on the rising edge of TI1FP1
+                    tim.smcr.modify(|_, w| unsafe { w.ts().bits(0b101).sms().bits(0b100) });
+
+                    // Load the prescaler
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Ok(PwmInput {
+                        tim,
+                        pin,
+                        clk: counter_clk(Hertz(tclk), psc),
+                    })
+                }
+
+                /// Returns the frequency the counter runs with
+                pub fn clock(&self) -> Hertz {
+                    self.clk
+                }
+
+                /// Returns the period of the last full cycle in counter ticks
+                ///
+                /// Returns `None` until a full period was captured.
+                pub fn period(&self) -> Option<u16> {
+                    match self.tim.ccr1.read().ccr().bits() as u16 {
+                        0 => None,
+                        period => Some(period),
+                    }
+                }
+
+                /// Returns the high time and the period of the last cycle in counter ticks
+                pub fn duty_cycle(&self) -> Option<(u16, u16)> {
+                    let period = self.period()?;
+                    Some((self.tim.ccr2.read().ccr().bits() as u16, period))
+                }
+
+                /// Returns the frequency of the input signal
+                pub fn frequency(&self) -> Option<Hertz> {
+                    self.period().map(|period| Hertz(self.clk.0 / u32::from(period)))
+                }
+
+                /// Returns `true` if a new period was captured since the last call
+                pub fn is_updated(&mut self) -> bool {
+                    let updated = self.tim.sr.read().cc1if().bit_is_set();
+                    self.tim.sr.modify(|_, w| w.cc1if().clear_bit().cc1of().clear_bit());
+                    updated
+                }
+
+                /// Disables the timer and releases the TIM peripheral and the pin
+                pub fn release(self) -> ($TIM, PIN) {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    (self.tim, self.pin)
+                }
+            }
+        )+
+    };
+}
+
+capture! {
+    TIM1: tim1, [
+        C1: (ccmr1_input, cc1s, ic1f, ic1psc, cc1e, cc1p, cc1np, cc1if, cc1of, cc1ie, ccr1),
+        C2: (ccmr1_input, cc2s, ic2f, ic2psc, cc2e, cc2p, cc2np, cc2if, cc2of, cc2ie, ccr2),
+        C3: (ccmr2_input, cc3s, ic3f, ic3psc, cc3e, cc3p, cc3np, cc3if, cc3of, cc3ie, ccr3),
+        C4: (ccmr2_input, cc4s, ic4f, ic4psc, cc4e, cc4p, cc4np, cc4if, cc4of, cc4ie, ccr4),
+    ],
+}
+
+pwm_input! {
+    TIM1: tim1,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+capture! {
+    TIM14: tim14, [
+        C1: (ccmr1_input, cc1s, ic1f, ic1psc, cc1e, cc1p, cc1np, cc1if, cc1of, cc1ie, ccr1),
+    ],
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+capture! {
+    TIM3: tim3, [
+        C1: (ccmr1_input, cc1s, ic1f, ic1psc, cc1e, cc1p, cc1np, cc1if, cc1of, cc1ie, ccr1),
+        C2: (ccmr1_input, cc2s, ic2f, ic2psc, cc2e, cc2p, cc2np, cc2if, cc2of, cc2ie, ccr2),
+        C3: (ccmr2_input, cc3s, ic3f, ic3psc, cc3e, cc3p, cc3np, cc3if, cc3of, cc3ie, ccr3),
+        C4: (ccmr2_input, cc4s, ic4f, ic4psc, cc4e, cc4p, cc4np, cc4if, cc4of, cc4ie, ccr4),
+    ],
+    TIM16: tim16, [
+        C1: (ccmr1_input, cc1s, ic1f, ic1psc, cc1e, cc1p, cc1np, cc1if, cc1of, cc1ie, ccr1),
+    ],
+    TIM17: tim17, [
+        C1: (ccmr1_input, cc1s, ic1f, ic1psc, cc1e, cc1p, cc1np, cc1if, cc1of, cc1ie, ccr1),
+    ],
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pwm_input! {
+    TIM3: tim3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_changes_keep_kernel_clock() {
+        let tclk = Hertz(24_000_000);
+        for _ in 0..3 {
+            for &resolution in &[3_500_000, 1_000_000, 367, 24_000_000, 7_000_000] {
+                let psc = compute_psc(tclk, Hertz(resolution)).unwrap();
+                let clk = counter_clk(tclk, psc);
+                assert!(clk.0 >= resolution, "{} Hz", resolution);
+                // The reached frequency selects the same prescaler again
+                assert_eq!(compute_psc(tclk, clk), Ok(psc), "{} Hz", resolution);
+            }
+        }
+        assert_eq!(counter_clk(tclk, 6).0, 3_428_571);
+        assert_eq!(compute_psc(tclk, tclk), Ok(0));
+    }
+}
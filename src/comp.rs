@@ -0,0 +1,469 @@
+//! API for the analog comparators (COMP1/COMP2)
+//!
+//! The comparators compare the voltage of a positive input pin against either a
+//! second pin or a fraction of the internal voltage reference. The result can be
+//! read back, routed to a GPIO alternate function or the TIM1 break and
+//! OCREF_CLR inputs, and trigger an interrupt through EXTI line 17 (COMP1) or
+//! 18 (COMP2).
+//!
+//! A VREF buffer input is not provided, other reference voltages have to be
+//! connected to one of the negative input pins.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::comp::{Comparator, Config, Edge, VrefintDiv2};
+//!
+//! cortex_m::interrupt::free(|cs| {
+//!     let mut p = pac::Peripherals::take().unwrap();
+//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+//!
+//!     let gpioa = p.GPIOA.split(&mut rcc);
+//!     let inp = gpioa.pa1.into_analog(cs);
+//!
+//!     // Compare PA1 against VREFINT / 2
+//!     let mut comp = Comparator::comp1(p.COMP1, (inp, VrefintDiv2), Config::default(), &mut rcc);
+//!     comp.listen(Edge::Rising, &p.EXTI);
+//!     comp.enable();
+//!
+//!     let above = comp.output();
+//! });
+//! ```
+
+use crate::gpio::{gpioa::*, gpiob::*, Alternate, Analog, AF7};
+use crate::pac::{COMP1, COMP2, EXTI, SYSCFG, TIM1};
+use crate::rcc::{Enable, Rcc, Reset};
+
+/// Comparator abstraction
+pub struct Comparator<COMP, PINS> {
+    comp: COMP,
+    pins: PINS,
+}
+
+/// Positive (non-inverting) input marker trait
+pub trait PositiveInput<COMP> {
+    /// Value of the `INPSEL` field selecting this input
+    const INPSEL: u8;
+}
+
+/// Negative (inverting) input marker trait
+pub trait NegativeInput<COMP> {
+    /// Value of the `INMSEL` field selecting this input
+    const INMSEL: u8;
+    /// The input is derived from VREFINT and needs the scaler enabled
+    const SCALER: bool = false;
+    /// The input is a fraction of VREFINT and needs the resistor bridge enabled
+    const BRIDGE: bool = false;
+}
+
+/// Output pin marker trait
+pub trait OutPin<COMP> {}
+
+/// 1/4 of the internal voltage reference as negative input
+pub struct VrefintDiv4;
+/// 1/2 of the internal voltage reference as negative input
+pub struct VrefintDiv2;
+/// 3/4 of the internal voltage reference as negative input
+pub struct Vrefint3Div4;
+/// The full internal voltage reference as negative input
+pub struct Vrefint;
+
+macro_rules! vrefint_inputs {
+    ($($COMP:ident),+) => {
+        $(
+            impl NegativeInput<$COMP> for VrefintDiv4 {
+                const INMSEL: u8 = 0b000;
+                const SCALER: bool = true;
+                const BRIDGE: bool = true;
+            }
+
+            impl NegativeInput<$COMP> for VrefintDiv2 {
+                const INMSEL: u8 = 0b001;
+                const SCALER: bool = true;
+                const BRIDGE: bool = true;
+            }
+
+            impl NegativeInput<$COMP> for Vrefint3Div4 {
+                const INMSEL: u8 = 0b010;
+                const SCALER: bool = true;
+                const BRIDGE: bool = true;
+            }
+
+            impl NegativeInput<$COMP> for Vrefint {
+                const INMSEL: u8 = 0b011;
+                const SCALER: bool = true;
+            }
+        )+
+    };
+}
+
+vrefint_inputs!(COMP1, COMP2);
+
+macro_rules! input_pins {
+    ($($trait:ident, $field:ident, $pin:ident, $COMP:ident, $sel:expr;)+) => {
+        $(
+            impl $trait<$COMP> for $pin<Analog> {
+                const $field: u8 = $sel;
+            }
+        )+
+    };
+}
+
+input_pins!(
+    PositiveInput, INPSEL, PB8, COMP1, 0b00;
+    PositiveInput, INPSEL, PB2, COMP1, 0b01;
+    PositiveInput, INPSEL, PA1, COMP1, 0b10;
+    NegativeInput, INMSEL, PB1, COMP1, 0b110;
+    NegativeInput, INMSEL, PA0, COMP1, 0b111;
+
+    PositiveInput, INPSEL, PB4, COMP2, 0b00;
+    PositiveInput, INPSEL, PB6, COMP2, 0b01;
+    PositiveInput, INPSEL, PA3, COMP2, 0b10;
+    NegativeInput, INMSEL, PB3, COMP2, 0b110;
+    NegativeInput, INMSEL, PA2, COMP2, 0b111;
+);
+
+macro_rules! out_pins {
+    ($($pin:ident, $af:ident, $COMP:ident;)+) => {
+        $(
+            impl OutPin<$COMP> for $pin<Alternate<$af>> {}
+        )+
+    };
+}
+
+out_pins!(
+    PA0, AF7, COMP1;
+    PA6, AF7, COMP1;
+    PA11, AF7, COMP1;
+
+    PA2, AF7, COMP2;
+    PA7, AF7, COMP2;
+    PA12, AF7, COMP2;
+);
+
+/// Comparator hysteresis
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hysteresis {
+    /// No hysteresis
+    None,
+    /// Hysteresis of about 20 mV
+    On,
+}
+
+/// Comparator power mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerMode {
+    /// Highest speed, highest consumption
+    HighSpeed,
+    /// Reduced speed and consumption
+    MediumSpeed,
+}
+
+impl PowerMode {
+    fn bits(self) -> u8 {
+        match self {
+            PowerMode::HighSpeed => 0b00,
+            PowerMode::MediumSpeed => 0b01,
+        }
+    }
+}
+
+/// Comparator output polarity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputPolarity {
+    /// Output is high when the positive input is above the negative input
+    NotInverted,
+    /// Output is high when the positive input is below the negative input
+    Inverted,
+}
+
+/// Blanking source masking the comparator output during current spikes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blanking {
+    /// No blanking
+    None,
+    /// TIM1 OC4 is used as blanking source
+    Tim1Oc4,
+}
+
+impl Blanking {
+    fn bits(self) -> u8 {
+        match self {
+            Blanking::None => 0b000,
+            Blanking::Tim1Oc4 => 0b001,
+        }
+    }
+}
+
+/// Internal destinations of the comparator output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputRoute {
+    /// TIM1 break input
+    Tim1Break,
+    /// TIM1 OCREF_CLR input
+    Tim1OcrefClr,
+}
+
+/// EXTI trigger edge of the comparator output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// The output changes from low to high
+    Rising,
+    /// The output changes from high to low
+    Falling,
+    /// The output changes in either direction
+    RisingFalling,
+}
+
+/// Comparator configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// Hysteresis of the inputs
+    pub hysteresis: Hysteresis,
+    /// Speed and consumption trade-off
+    pub power_mode: PowerMode,
+    /// Polarity of the output
+    pub polarity: OutputPolarity,
+    /// Source masking the output
+    pub blanking: Blanking,
+    /// Digital filter length in PCLK cycles, `0` disables the filter
+    pub filter: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            hysteresis: Hysteresis::None,
+            power_mode: PowerMode::HighSpeed,
+            polarity: OutputPolarity::NotInverted,
+            blanking: Blanking::None,
+            filter: 0,
+        }
+    }
+}
+
+impl Config {
+    pub fn hysteresis(mut self, hysteresis: Hysteresis) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    pub fn power_mode(mut self, power_mode: PowerMode) -> Self {
+        self.power_mode = power_mode;
+        self
+    }
+
+    pub fn polarity(mut self, polarity: OutputPolarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn blanking(mut self, blanking: Blanking) -> Self {
+        self.blanking = blanking;
+        self
+    }
+
+    pub fn filter(mut self, filter: u16) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+macro_rules! comp {
+    ($($COMP:ident: ($comp:ident, $line:expr, $brk_tim1:ident),)+) => {
+        $(
+            impl<INP, INM> Comparator<$COMP, (INP, INM)>
+            where
+                INP: PositiveInput<$COMP>,
+                INM: NegativeInput<$COMP>,
+            {
+                /// Creates a new comparator instance, left disabled
+                pub fn $comp(comp: $COMP, pins: (INP, INM), config: Config, rcc: &mut Rcc) -> Self {
+                    // Enable clock for COMP and SYSCFG (output routing)
+                    $COMP::enable(rcc);
+                    SYSCFG::enable(rcc);
+
+                    // Reset COMP
+                    $COMP::reset(rcc);
+
+                    comp.csr.write(|w| unsafe {
+                        w.inpsel()
+                            .bits(INP::INPSEL)
+                            .inmsel()
+                            .bits(INM::INMSEL)
+                            .scaler_en()
+                            .bit(INM::SCALER)
+                            .brgen()
+                            .bit(INM::BRIDGE)
+                            .hyst()
+                            .bit(config.hysteresis == Hysteresis::On)
+                            .pwrmode()
+                            .bits(config.power_mode.bits())
+                            .polarity()
+                            .bit(config.polarity == OutputPolarity::Inverted)
+                            .blanking()
+                            .bits(config.blanking.bits())
+                    });
+
+                    if config.filter != 0 {
+                        comp.fr.write(|w| unsafe { w.fltcnt().bits(config.filter).flten().set_bit() });
+                    }
+
+                    Comparator { comp, pins }
+                }
+            }
+
+            impl<PINS> Comparator<$COMP, PINS> {
+                /// Enables the comparator
+                pub fn enable(&mut self) {
+                    self.comp.csr.modify(|_, w| w.en().set_bit());
+                }
+
+                /// Disables the comparator
+                pub fn disable(&mut self) {
+                    self.comp.csr.modify(|_, w| w.en().clear_bit());
+                }
+
+                /// Returns the comparator output level, after polarity selection
+                pub fn output(&self) -> bool {
+                    self.comp.csr.read().comp_out().bit_is_set()
+                }
+
+                /// Sets the output polarity
+                pub fn set_polarity(&mut self, polarity: OutputPolarity) {
+                    self.comp
+                        .csr
+                        .modify(|_, w| w.polarity().bit(polarity == OutputPolarity::Inverted));
+                }
+
+                /// Sets the hysteresis
+                pub fn set_hysteresis(&mut self, hysteresis: Hysteresis) {
+                    self.comp
+                        .csr
+                        .modify(|_, w| w.hyst().bit(hysteresis == Hysteresis::On));
+                }
+
+                /// Sets the power mode
+                pub fn set_power_mode(&mut self, power_mode: PowerMode) {
+                    self.comp
+                        .csr
+                        .modify(|_, w| unsafe { w.pwrmode().bits(power_mode.bits()) });
+                }
+
+                /// Sets the blanking source
+                pub fn set_blanking(&mut self, blanking: Blanking) {
+                    self.comp
+                        .csr
+                        .modify(|_, w| unsafe { w.blanking().bits(blanking.bits()) });
+                }
+
+                /// Connects the comparator output to an internal TIM1 input
+                pub fn connect(&mut self, route: OutputRoute) {
+                    match route {
+                        // NOTE(unsafe) SYSCFG is only used for routing and its clock is enabled in the constructor
+                        OutputRoute::Tim1Break => unsafe {
+                            (*SYSCFG::ptr()).cfgr2.modify(|_, w| w.$brk_tim1().set_bit())
+                        },
+                        // NOTE(unsafe) OCREF_CLR_INT is hardwired to the comparator outputs, only the source is selected
+                        OutputRoute::Tim1OcrefClr => unsafe {
+                            (*TIM1::ptr()).smcr.modify(|_, w| w.occs().clear_bit())
+                        },
+                    }
+                }
+
+                /// Disconnects the comparator output from an internal TIM1 input
+                pub fn disconnect(&mut self, route: OutputRoute) {
+                    match route {
+                        // NOTE(unsafe) SYSCFG is only used for routing and its clock is enabled in the constructor
+                        OutputRoute::Tim1Break => unsafe {
+                            (*SYSCFG::ptr()).cfgr2.modify(|_, w| w.$brk_tim1().clear_bit())
+                        },
+                        // NOTE(unsafe) select ETRF as OCREF_CLR source again
+                        OutputRoute::Tim1OcrefClr => unsafe {
+                            (*TIM1::ptr()).smcr.modify(|_, w| w.occs().set_bit())
+                        },
+                    }
+                }
+
+                /// Starts listening for output changes on the comparator EXTI line
+                pub fn listen(&mut self, edge: Edge, exti: &EXTI) {
+                    let (rising, falling) = match edge {
+                        Edge::Rising => (true, false),
+                        Edge::Falling => (false, true),
+                        Edge::RisingFalling => (true, true),
+                    };
+                    // NOTE(unsafe) only the bit of this comparator line is modified
+                    unsafe {
+                        exti.rtsr.modify(|r, w| {
+                            w.bits(if rising { r.bits() | (1 << $line) } else { r.bits() & !(1 << $line) })
+                        });
+                        exti.ftsr.modify(|r, w| {
+                            w.bits(if falling { r.bits() | (1 << $line) } else { r.bits() & !(1 << $line) })
+                        });
+                        exti.imr.modify(|r, w| w.bits(r.bits() | (1 << $line)));
+                    }
+                }
+
+                /// Stops listening for output changes
+                pub fn unlisten(&mut self, exti: &EXTI) {
+                    // NOTE(unsafe) only the bit of this comparator line is modified
+                    exti.imr.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << $line)) });
+                }
+
+                /// Returns true if an output change is pending on the EXTI line
+                pub fn is_pending(&self, exti: &EXTI) -> bool {
+                    exti.pr.read().bits() & (1 << $line) != 0
+                }
+
+                /// Clears the pending EXTI line interrupt
+                pub fn clear_irq(&mut self, exti: &EXTI) {
+                    // NOTE(unsafe) write 1 to clear, other lines are not affected
+                    exti.pr.write(|w| unsafe { w.bits(1 << $line) });
+                }
+
+                /// Locks the comparator configuration until the next system reset
+                pub fn lock(self) -> Self {
+                    self.comp.csr.modify(|_, w| w.lock().set_bit());
+                    self
+                }
+
+                /// Routes the comparator output to a GPIO alternate function
+                pub fn output_pin<OUT>(self, out: OUT) -> Comparator<$COMP, (PINS, OUT)>
+                where
+                    OUT: OutPin<$COMP>,
+                {
+                    Comparator {
+                        comp: self.comp,
+                        pins: (self.pins, out),
+                    }
+                }
+
+                /// Disables the comparator and releases the peripheral and the pins
+                pub fn release(self) -> ($COMP, PINS) {
+                    self.comp.csr.modify(|_, w| w.en().clear_bit());
+                    // Disable the comparator clock
+                    unsafe { $COMP::disable_unchecked() };
+                    (self.comp, self.pins)
+                }
+            }
+        )+
+    };
+}
+
+comp! {
+    COMP1: (comp1, 17, comp1_brk_tim1),
+    COMP2: (comp2, 18, comp2_brk_tim1),
+}
+
+impl<PINS> Comparator<COMP2, PINS> {
+    /// Enables window mode
+    ///
+    /// The positive input of COMP2 is connected to the positive input of COMP1, so
+    /// that both comparators can supervise one signal against two thresholds.
+    pub fn set_window_mode(&mut self, enable: bool) {
+        self.comp.csr.modify(|_, w| w.winmode().bit(enable));
+    }
+}
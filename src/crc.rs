@@ -0,0 +1,238 @@
+//! API for the CRC calculation unit
+//!
+//! The unit computes a CRC-32 with the polynomial `0x04C11DB7`, an initial value
+//! of `0xFFFF_FFFF`, no reflection and no final xor, which is the CRC-32/MPEG-2
+//! variant. Data is processed a word at a time, most significant byte first.
+//!
+//! The hardware has no init register. Other initial values (e.g. to continue a
+//! checksum computed earlier) are loaded by feeding it a word that leaves the
+//! unit in the requested state.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::crc::Crc;
+//!
+//! let mut p = pac::Peripherals::take().unwrap();
+//! let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+//!
+//! let mut crc = Crc::new(p.CRC, &mut rcc);
+//!
+//! crc.update(b"123456789");
+//! assert_eq!(crc.finish(), 0x0376_E6E7);
+//! ```
+
+use crate::pac::CRC;
+use crate::rcc::{Enable, Rcc};
+
+/// CRC-32/MPEG-2 polynomial used by the hardware
+pub const POLYNOMIAL: u32 = 0x04C1_1DB7;
+
+/// Value of the CRC unit after a reset
+pub const INITIAL_VALUE: u32 = 0xFFFF_FFFF;
+
+/// CRC calculation unit
+pub struct Crc {
+    crc: CRC,
+}
+
+impl Crc {
+    /// Enables and resets the CRC calculation unit
+    pub fn new(crc: CRC, rcc: &mut Rcc) -> Self {
+        // Enable clock for CRC
+        CRC::enable(rcc);
+
+        let mut crc = Crc { crc };
+        crc.reset();
+        crc
+    }
+
+    /// Resets the unit to the default initial value `0xFFFF_FFFF`
+    pub fn reset(&mut self) {
+        self.crc.cr.write(|w| w.reset().set_bit());
+    }
+
+    /// Resets the unit and loads `init` as the initial value
+    ///
+    /// This can be used to continue a checksum whose intermediate value was
+    /// saved with `finish`.
+    pub fn reset_with_initial_value(&mut self, init: u32) {
+        self.reset();
+        self.load(init);
+    }
+
+    /// Feeds bytes into the unit
+    ///
+    /// Bytes are processed in the order given, as CRC-32/MPEG-2 expects it.
+    pub fn update(&mut self, data: &[u8]) {
+        let mut chunks = data.chunks_exact(4);
+        for chunk in &mut chunks {
+            let word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            self.write(word);
+        }
+
+        // The unit only processes full words, finish the remaining bytes in
+        // software and load the result back
+        let remainder = chunks.remainder();
+        if !remainder.is_empty() {
+            let value = remainder
+                .iter()
+                .fold(self.finish(), |value, byte| update_byte(value, *byte));
+            self.reset_with_initial_value(value);
+        }
+    }
+
+    /// Feeds words into the unit
+    pub fn update_words(&mut self, data: &[u32]) {
+        for word in data {
+            self.write(*word);
+        }
+    }
+
+    /// Returns the checksum of the data fed since the last reset
+    ///
+    /// This does not reset the unit, more data can be fed afterwards.
+    pub fn finish(&self) -> u32 {
+        self.crc.dr.read().bits()
+    }
+
+    /// Starts a new CRC-32/MPEG-2 digest
+    ///
+    /// The digest mirrors the `Digest` of the `crc` crate with
+    /// `crc::CRC_32_MPEG_2`, so it can be used as a drop-in replacement.
+    pub fn digest(&mut self) -> Digest<'_> {
+        self.reset();
+        Digest { crc: self }
+    }
+
+    /// Starts a new digest with a custom initial value
+    pub fn digest_with_initial(&mut self, init: u32) -> Digest<'_> {
+        self.reset_with_initial_value(init);
+        Digest { crc: self }
+    }
+
+    /// Reads the independent data register
+    pub fn read_idr(&self) -> u8 {
+        self.crc.idr.read().bits() as u8
+    }
+
+    /// Writes the independent data register, a general purpose byte of storage
+    pub fn write_idr(&mut self, value: u8) {
+        self.crc.idr.write(|w| unsafe { w.bits(u32::from(value)) });
+    }
+
+    /// Disables the CRC calculation unit and releases it
+    pub fn release(self) -> CRC {
+        unsafe { CRC::disable_unchecked() };
+        self.crc
+    }
+
+    fn write(&mut self, word: u32) {
+        self.crc.dr.write(|w| unsafe { w.bits(word) });
+    }
+
+    /// Brings the unit from its reset value into the state `value`
+    fn load(&mut self, value: u32) {
+        self.write(initial_word(value));
+    }
+}
+
+/// Feeds a single byte into the CRC `value` in software
+fn update_byte(mut value: u32, byte: u8) -> u32 {
+    value ^= u32::from(byte) << 24;
+    for _ in 0..8 {
+        value = if value & 0x8000_0000 != 0 {
+            (value << 1) ^ POLYNOMIAL
+        } else {
+            value << 1
+        };
+    }
+    value
+}
+
+/// Returns the word which brings the unit from its reset value into the state `value`
+fn initial_word(value: u32) -> u32 {
+    // Each word is xored into the state, followed by 32 shift steps. The shift
+    // steps are invertible as the polynomial has the x^0 term set, so run them
+    // backwards to find the word that results in `value`.
+    let mut state = value;
+    for _ in 0..32 {
+        state = if state & 1 != 0 {
+            ((state ^ POLYNOMIAL) >> 1) | 0x8000_0000
+        } else {
+            state >> 1
+        };
+    }
+    state ^ INITIAL_VALUE
+}
+
+/// A CRC-32/MPEG-2 digest computed by the CRC calculation unit
+pub struct Digest<'a> {
+    crc: &'a mut Crc,
+}
+
+impl<'a> Digest<'a> {
+    /// Feeds bytes into the digest
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.crc.update(bytes);
+    }
+
+    /// Returns the checksum of all fed bytes
+    pub fn finalize(self) -> u32 {
+        self.crc.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Software model of a word written to the data register
+    fn update_word(value: u32, word: u32) -> u32 {
+        word.to_be_bytes()
+            .iter()
+            .fold(value, |value, byte| update_byte(value, *byte))
+    }
+
+    #[test]
+    fn mpeg2_check_value() {
+        let crc = b"123456789"
+            .iter()
+            .fold(INITIAL_VALUE, |value, byte| update_byte(value, *byte));
+        assert_eq!(crc, 0x0376_E6E7);
+    }
+
+    #[test]
+    fn mpeg2_vectors() {
+        let crc = |data: &[u8]| {
+            data.iter()
+                .fold(INITIAL_VALUE, |value, byte| update_byte(value, *byte))
+        };
+        assert_eq!(crc(b""), 0xFFFF_FFFF);
+        assert_eq!(crc(&[0x00, 0x00, 0x00, 0x00]), 0xC704_DD7B);
+        assert_eq!(crc(&[0xFF, 0xFF, 0xFF, 0xFF]), 0x0000_0000);
+    }
+
+    #[test]
+    fn initial_word_loads_value() {
+        for &value in &[0, 1, 0x8000_0000, 0x0376_E6E7, 0xDEAD_BEEF, INITIAL_VALUE] {
+            assert_eq!(update_word(INITIAL_VALUE, initial_word(value)), value);
+        }
+    }
+
+    #[test]
+    fn continued_checksum() {
+        // Feeding "1234", saving the state and continuing with "5678" from a reloaded
+        // unit gives the same result as feeding "12345678" at once
+        let first = update_word(INITIAL_VALUE, u32::from_be_bytes(*b"1234"));
+        let loaded = update_word(INITIAL_VALUE, initial_word(first));
+        let continued = update_word(loaded, u32::from_be_bytes(*b"5678"));
+        let direct = b"12345678"
+            .iter()
+            .fold(INITIAL_VALUE, |value, byte| update_byte(value, *byte));
+        assert_eq!(continued, direct);
+    }
+}
@@ -18,7 +18,7 @@
 //! let mut p = pac::Peripherals::take().unwrap();
 //! let mut cp = cortex_m::Peripherals::take().unwrap();
 //!
-//! let mut rcc = p.RCC.configure().freeze(&mut p.FLASH);
+//! let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
 //! let mut delay = Delay::new(cp.SYST, &rcc);
 //! loop {
 //!     delay.delay_ms(1_000_u16);
@@ -29,14 +29,14 @@ use cast::{u16, u32};
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::SYST;
 
-use crate::rcc::Rcc;
+use crate::rcc::{ClockDependent, Clocks, Rcc};
 
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
 
 /// System timer (SysTick) as a delay provider
 #[derive(Clone)]
 pub struct Delay {
-    scale: u32,
+    hclk: u32,
 }
 
 const SYSTICK_RANGE: u32 = 0x0100_0000;
@@ -50,15 +50,21 @@ impl Delay {
         syst.clear_current();
         syst.enable_counter();
 
-        assert!(rcc.clocks.hclk().0 >= 1_000_000);
-        let scale = rcc.clocks.hclk().0 / 1_000_000;
-
-        Delay { scale }
+        let mut delay = Delay { hclk: 0 };
+        delay.update_clocks(&rcc.clocks);
+        delay
         // As access to the count register is possible without a reference to the systick, we can
         // just drop it
     }
 }
 
+impl ClockDependent for Delay {
+    fn update_clocks(&mut self, clocks: &Clocks) {
+        // Clocks below 1 MHz are valid as well, so the ticks are computed per delay
+        self.hclk = clocks.hclk().0;
+    }
+}
+
 impl DelayMs<u32> for Delay {
     // At 48 MHz (the maximum frequency), calling delay_us with ms * 1_000 directly overflows at 0x15D86 (just over the max u16 value)
     // So we implement a separate, higher level, delay loop
@@ -93,17 +99,17 @@ impl DelayUs<u32> for Delay {
         // Here less than maximum is used so we have some play if there's a long running interrupt.
         const MAX_TICKS: u32 = 0x007F_FFFF;
 
-        let mut total_ticks = us * self.scale;
+        let mut total_ticks = u64::from(us) * u64::from(self.hclk) / 1_000_000;
 
         while total_ticks != 0 {
-            let current_ticks = if total_ticks <= MAX_TICKS {
-                total_ticks
+            let current_ticks = if total_ticks <= u64::from(MAX_TICKS) {
+                total_ticks as u32
             } else {
                 MAX_TICKS
             };
 
             let start_count = SYST::get_current();
-            total_ticks -= current_ticks;
+            total_ticks -= u64::from(current_ticks);
 
             // Use the wrapping substraction and the modulo to deal with the systick wrapping around
             // from 0 to 0xFFFF
@@ -0,0 +1,664 @@
+//! API for the embedded flash memory
+//!
+//! The flash is erased in pages of 128 bytes or sectors of 4 KiB and programmed a
+//! full page at a time. All offsets are relative to the start of the flash memory
+//! (`0x0800_0000`) and bounds are checked against the size selected with the
+//! `flash-*` features.
+//!
+//! Erasing or programming stalls the CPU if it fetches code from the flash at the
+//! same time, so keep an eye on interrupt latency.
+//!
+//! The user option bytes (read-out protection, brown-out reset, watchdog start,
+//! NRST pin and boot configuration) are read with `Flash::option_bytes` and
+//! written with `UnlockedFlash::program_option_bytes`.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::flash::{Flash, PAGE_SIZE};
+//!
+//! let mut p = pac::Peripherals::take().unwrap();
+//! let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+//!
+//! let mut flash = Flash::new(p.FLASH);
+//!
+//! // Store configuration in the last page
+//! let offset = flash.capacity() as u32 - PAGE_SIZE as u32;
+//! let mut page = [0xFF; PAGE_SIZE];
+//! page[..4].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+//!
+//! let mut unlocked = flash.unlock();
+//! unlocked.erase_page(offset).unwrap();
+//! unlocked.program_page(offset, &page).unwrap();
+//! ```
+
+use core::{ptr, slice};
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+use crate::pac::FLASH;
+
+/// Start address of the flash memory
+pub const FLASH_START: u32 = 0x0800_0000;
+
+/// Size of the flash memory in bytes, as selected by the `flash-*` features
+///
+/// Without any of these features the smallest size of the device family is used.
+pub const FLASH_SIZE: usize = if cfg!(feature = "flash-64") {
+    64 * 1024
+} else if cfg!(feature = "flash-48") {
+    48 * 1024
+} else if cfg!(feature = "flash-32") {
+    32 * 1024
+} else if cfg!(feature = "flash-24") {
+    24 * 1024
+} else if cfg!(feature = "flash-20") {
+    20 * 1024
+} else if cfg!(feature = "flash-16") {
+    16 * 1024
+} else if cfg!(feature = "py32f002b") {
+    24 * 1024
+} else if cfg!(feature = "py32f002a") {
+    20 * 1024
+} else {
+    16 * 1024
+};
+
+/// Size of a page, the unit of programming and the smallest erasable unit
+pub const PAGE_SIZE: usize = 128;
+
+/// Size of a sector
+pub const SECTOR_SIZE: usize = 4 * 1024;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+const OPTKEY1: u32 = 0x0819_2A3B;
+const OPTKEY2: u32 = 0x4C5D_6E7F;
+
+// Writing to this address starts programming the option bytes once OPTSTRT is set
+const OPTION_BYTES_TRIGGER: u32 = 0x4002_2080;
+
+// Flash timing parameters, one row per HSI_FS setting, stored in the configuration bytes right
+// after the VREF and temperature sensor calibration values
+const TIMING_BASE: u32 = 0x1FFF_0F1C;
+const TIMING_STRIDE: u32 = 0x14;
+
+/// Flash error
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The address or length is not a multiple of the page or sector size
+    Alignment,
+    /// The address range exceeds the flash memory
+    OutOfBounds,
+    /// The target is write protected
+    WriteProtection,
+    /// The operation did not complete
+    Program,
+    /// The option bytes were rejected or do not read back as written
+    OptionBytes,
+}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::Alignment => NorFlashErrorKind::NotAligned,
+            Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// Flash memory
+pub struct Flash {
+    flash: FLASH,
+}
+
+impl Flash {
+    pub fn new(flash: FLASH) -> Self {
+        Flash { flash }
+    }
+
+    /// Returns the size of the flash memory in bytes
+    pub fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+
+    /// Returns the flash contents at `offset` as a slice
+    pub fn read_slice(&self, offset: u32, len: usize) -> Result<&[u8], Error> {
+        check_bounds(offset, len)?;
+        // NOTE(unsafe) the range is within the memory mapped flash
+        Ok(unsafe { slice::from_raw_parts((FLASH_START + offset) as *const u8, len) })
+    }
+
+    /// Reads the option bytes from the OPTR register
+    pub fn option_bytes(&self) -> OptionBytes {
+        OptionBytes::from_bits(self.flash.optr.read().bits())
+    }
+
+    /// Unlocks the flash for erasing and programming
+    ///
+    /// The flash is locked again when the returned value is dropped.
+    pub fn unlock(&mut self) -> UnlockedFlash<'_> {
+        unlock(&self.flash);
+        load_timing(&self.flash);
+        UnlockedFlash { flash: &self.flash }
+    }
+
+    /// Gives access to the FLASH registers, e.g. for [`Rcc::reconfigure`]
+    ///
+    /// [`Rcc::reconfigure`]: crate::rcc::Rcc::reconfigure
+    pub fn regs_mut(&mut self) -> &mut FLASH {
+        &mut self.flash
+    }
+
+    /// Releases the FLASH peripheral
+    pub fn release(self) -> FLASH {
+        self.flash
+    }
+}
+
+/// Unlocked flash memory, locked again on drop
+pub struct UnlockedFlash<'a> {
+    flash: &'a FLASH,
+}
+
+impl<'a> Drop for UnlockedFlash<'a> {
+    fn drop(&mut self) {
+        lock(self.flash);
+    }
+}
+
+impl<'a> UnlockedFlash<'a> {
+    /// Erases the page containing `offset`, which must be page aligned
+    pub fn erase_page(&mut self, offset: u32) -> Result<(), Error> {
+        check_aligned(offset, PAGE_SIZE)?;
+        check_bounds(offset, PAGE_SIZE)?;
+
+        self.flash
+            .cr
+            .modify(|_, w| w.per().set_bit().eopie().set_bit());
+        let result = self.erase(offset);
+        self.flash
+            .cr
+            .modify(|_, w| w.per().clear_bit().eopie().clear_bit());
+        result
+    }
+
+    /// Erases the sector containing `offset`, which must be sector aligned
+    pub fn erase_sector(&mut self, offset: u32) -> Result<(), Error> {
+        check_aligned(offset, SECTOR_SIZE)?;
+        check_bounds(offset, SECTOR_SIZE)?;
+
+        self.flash
+            .cr
+            .modify(|_, w| w.ser().set_bit().eopie().set_bit());
+        let result = self.erase(offset);
+        self.flash
+            .cr
+            .modify(|_, w| w.ser().clear_bit().eopie().clear_bit());
+        result
+    }
+
+    /// Erases the range `from..to`, using sector erases where possible
+    pub fn erase_range(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        if from > to {
+            return Err(Error::OutOfBounds);
+        }
+        check_aligned(from, PAGE_SIZE)?;
+        check_aligned(to, PAGE_SIZE)?;
+        check_bounds(from, (to - from) as usize)?;
+
+        let mut offset = from;
+        while offset < to {
+            if offset as usize % SECTOR_SIZE == 0 && (to - offset) as usize >= SECTOR_SIZE {
+                self.erase_sector(offset)?;
+                offset += SECTOR_SIZE as u32;
+            } else {
+                self.erase_page(offset)?;
+                offset += PAGE_SIZE as u32;
+            }
+        }
+        Ok(())
+    }
+
+    /// Programs an erased page with `data`
+    pub fn program_page(&mut self, offset: u32, data: &[u8; PAGE_SIZE]) -> Result<(), Error> {
+        check_aligned(offset, PAGE_SIZE)?;
+        check_bounds(offset, PAGE_SIZE)?;
+
+        self.wait_ready();
+        self.clear_flags();
+        // EOP is only flagged with its interrupt enabled
+        self.flash
+            .cr
+            .modify(|_, w| w.pg().set_bit().eopie().set_bit());
+
+        // The page buffer is filled word by word, the programming starts with the
+        // last word once PGSTRT is set. Nothing may touch the flash in between.
+        cortex_m::interrupt::free(|_| {
+            let dest = (FLASH_START + offset) as *mut u32;
+            for (i, word) in data.chunks_exact(4).enumerate() {
+                if i == PAGE_SIZE / 4 - 1 {
+                    self.flash.cr.modify(|_, w| w.pgstrt().set_bit());
+                }
+                let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                // NOTE(unsafe) the page is within the flash memory
+                unsafe { ptr::write_volatile(dest.add(i), word) };
+            }
+        });
+
+        let result = self.wait_done();
+        self.flash
+            .cr
+            .modify(|_, w| w.pg().clear_bit().eopie().clear_bit());
+        result?;
+
+        // Read back to make sure the page was programmed correctly
+        let written =
+            unsafe { slice::from_raw_parts((FLASH_START + offset) as *const u8, PAGE_SIZE) };
+        if written != &data[..] {
+            return Err(Error::Program);
+        }
+        Ok(())
+    }
+
+    /// Programs consecutive erased pages starting at `offset`
+    ///
+    /// The length of `data` must be a multiple of the page size.
+    pub fn program(&mut self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        check_aligned(offset, PAGE_SIZE)?;
+        check_aligned(data.len() as u32, PAGE_SIZE)?;
+        check_bounds(offset, data.len())?;
+
+        let mut page = [0; PAGE_SIZE];
+        for (i, chunk) in data.chunks_exact(PAGE_SIZE).enumerate() {
+            page.copy_from_slice(chunk);
+            self.program_page(offset + (i * PAGE_SIZE) as u32, &page)?;
+        }
+        Ok(())
+    }
+
+    /// Programs the option bytes
+    ///
+    /// The new values only take effect after a reload with `launch_option_bytes`
+    /// or the next power-on reset. Nothing is written if `ob` matches the OPTR
+    /// register.
+    ///
+    /// **Attention**: changing the read-out protection from level 1 back to level
+    /// 0 mass erases the flash.
+    pub fn program_option_bytes(&mut self, ob: &OptionBytes) -> Result<(), Error> {
+        let current = self.flash.optr.read().bits();
+        let value = (current & !OptionBytes::MASK) | ob.bits();
+        if value == current {
+            return Ok(());
+        }
+
+        self.wait_ready();
+        self.clear_flags();
+        unlock_option_bytes(self.flash);
+
+        self.flash.optr.write(|w| unsafe { w.bits(value) });
+        self.flash
+            .cr
+            .modify(|_, w| w.optstrt().set_bit().eopie().set_bit());
+        // NOTE(unsafe) the trigger address accepts any value
+        unsafe { ptr::write_volatile(OPTION_BYTES_TRIGGER as *mut u32, 0xFFFF_FFFF) };
+
+        let result = self.wait_done();
+        self.flash
+            .cr
+            .modify(|_, w| w.optstrt().clear_bit().eopie().clear_bit());
+
+        let sr = self.flash.sr.read();
+        if sr.optverr().bit_is_set() {
+            // NOTE(unsafe) write 1 to clear OPTVERR
+            self.flash.sr.write(|w| unsafe { w.bits(1 << 15) });
+            return Err(Error::OptionBytes);
+        }
+        result?;
+
+        if self.flash.optr.read().bits() & OptionBytes::MASK != ob.bits() {
+            return Err(Error::OptionBytes);
+        }
+        Ok(())
+    }
+
+    /// Reloads the option bytes, which resets the device
+    pub fn launch_option_bytes(self) -> ! {
+        unlock_option_bytes(self.flash);
+        self.flash.cr.modify(|_, w| w.obl_launch().set_bit());
+        // The reload resets the device
+        loop {
+            cortex_m::asm::nop();
+        }
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), Error> {
+        self.wait_ready();
+        self.clear_flags();
+
+        // Writing any value to an address in the page or sector starts the erase
+        // NOTE(unsafe) the address is within the flash memory
+        unsafe { ptr::write_volatile((FLASH_START + offset) as *mut u32, 0xFFFF_FFFF) };
+
+        self.wait_done()
+    }
+
+    fn wait_ready(&self) {
+        while self.flash.sr.read().bsy().bit_is_set() {}
+    }
+
+    fn wait_done(&self) -> Result<(), Error> {
+        self.wait_ready();
+
+        let sr = self.flash.sr.read();
+        if sr.wrperr().bit_is_set() {
+            self.clear_flags();
+            Err(Error::WriteProtection)
+        } else if sr.eop().bit_is_set() {
+            self.clear_flags();
+            Ok(())
+        } else {
+            Err(Error::Program)
+        }
+    }
+
+    fn clear_flags(&self) {
+        // NOTE(unsafe) write 1 to clear EOP and WRPERR
+        self.flash
+            .sr
+            .write(|w| unsafe { w.bits((1 << 0) | (1 << 4)) });
+    }
+}
+
+fn unlock(flash: &FLASH) {
+    if flash.cr.read().lock().bit_is_set() {
+        // NOTE(unsafe) the key sequence is defined by the reference manual
+        flash.keyr.write(|w| unsafe { w.bits(KEY1) });
+        flash.keyr.write(|w| unsafe { w.bits(KEY2) });
+    }
+}
+
+fn unlock_option_bytes(flash: &FLASH) {
+    if flash.cr.read().optlock().bit_is_set() {
+        // NOTE(unsafe) the key sequence is defined by the reference manual
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTKEY1) });
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTKEY2) });
+    }
+}
+
+fn lock(flash: &FLASH) {
+    flash
+        .cr
+        .modify(|_, w| w.optlock().set_bit().lock().set_bit());
+}
+
+/// Loads the erase and program timings matching the current HSI frequency
+fn load_timing(flash: &FLASH) {
+    // NOTE(unsafe) atomic read with no side effects
+    let hsi_fs = unsafe { (*crate::pac::RCC::ptr()).icscr.read().hsi_fs().bits() };
+    let base = (TIMING_BASE + u32::from(hsi_fs) * TIMING_STRIDE) as *const u32;
+
+    // NOTE(unsafe) the configuration bytes are always readable
+    let para = unsafe {
+        [
+            ptr::read(base),
+            ptr::read(base.add(1)),
+            ptr::read(base.add(2)),
+            ptr::read(base.add(3)),
+            ptr::read(base.add(4)),
+        ]
+    };
+
+    unsafe {
+        flash.ts0.write(|w| w.bits(para[0] & 0xFF));
+        flash.ts1.write(|w| w.bits((para[0] >> 16) & 0x1FF));
+        flash.ts3.write(|w| w.bits((para[0] >> 8) & 0xFF));
+        flash.ts2p.write(|w| w.bits(para[1] & 0xFF));
+        flash.tps3.write(|w| w.bits((para[1] >> 16) & 0x7FF));
+        flash.pertpe.write(|w| w.bits(para[2] & 0x1_FFFF));
+        flash.smertpe.write(|w| w.bits(para[3] & 0x1_FFFF));
+        flash.prgtpe.write(|w| w.bits(para[4] & 0xFFFF));
+        flash.pretpe.write(|w| w.bits((para[4] >> 16) & 0x3FFF));
+    }
+}
+
+/// Read-out protection level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReadProtection {
+    /// No protection
+    Level0,
+    /// Flash can not be read by the debugger or the bootloader
+    Level1,
+}
+
+/// Brown-out reset threshold, as rising / falling voltage
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BorLevel {
+    /// 1.8 V / 1.7 V
+    V1_8 = 0,
+    /// 2.0 V / 1.9 V
+    V2_0 = 1,
+    /// 2.2 V / 2.1 V
+    V2_2 = 2,
+    /// 2.4 V / 2.3 V
+    V2_4 = 3,
+    /// 2.6 V / 2.5 V
+    V2_6 = 4,
+    /// 2.8 V / 2.7 V
+    V2_8 = 5,
+    /// 3.0 V / 2.9 V
+    V3_0 = 6,
+    /// 3.2 V / 3.1 V
+    V3_2 = 7,
+}
+
+impl BorLevel {
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0b111 {
+            0 => BorLevel::V1_8,
+            1 => BorLevel::V2_0,
+            2 => BorLevel::V2_2,
+            3 => BorLevel::V2_4,
+            4 => BorLevel::V2_6,
+            5 => BorLevel::V2_8,
+            6 => BorLevel::V3_0,
+            _ => BorLevel::V3_2,
+        }
+    }
+}
+
+/// Function of the NRST pin
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NrstMode {
+    /// Reset input
+    Reset,
+    /// General purpose IO
+    Gpio,
+}
+
+/// User option bytes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    /// Brown-out reset threshold, `None` disables the brown-out reset
+    pub bor: Option<BorLevel>,
+    /// The IWDG is started by software, otherwise by hardware after reset
+    pub iwdg_sw: bool,
+    /// The WWDG is started by software, otherwise by hardware after reset
+    pub wwdg_sw: bool,
+    pub nrst_mode: NrstMode,
+    /// Boot selection in combination with the BOOT0 pin
+    pub nboot1: bool,
+}
+
+impl OptionBytes {
+    const MASK: u32 = 0xFFFF;
+
+    const RDP_LEVEL0: u32 = 0xAA;
+    const RDP_LEVEL1: u32 = 0x55;
+
+    fn from_bits(bits: u32) -> Self {
+        OptionBytes {
+            read_protection: if bits & 0xFF == Self::RDP_LEVEL0 {
+                ReadProtection::Level0
+            } else {
+                ReadProtection::Level1
+            },
+            bor: if bits & (1 << 8) != 0 {
+                Some(BorLevel::from_bits(bits >> 9))
+            } else {
+                None
+            },
+            iwdg_sw: bits & (1 << 12) != 0,
+            wwdg_sw: bits & (1 << 13) != 0,
+            nrst_mode: if bits & (1 << 14) != 0 {
+                NrstMode::Gpio
+            } else {
+                NrstMode::Reset
+            },
+            nboot1: bits & (1 << 15) != 0,
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        let rdp = match self.read_protection {
+            ReadProtection::Level0 => Self::RDP_LEVEL0,
+            ReadProtection::Level1 => Self::RDP_LEVEL1,
+        };
+        let bor = match self.bor {
+            Some(level) => (1 << 8) | ((level as u32) << 9),
+            None => 0,
+        };
+        rdp | bor
+            | (u32::from(self.iwdg_sw) << 12)
+            | (u32::from(self.wwdg_sw) << 13)
+            | (u32::from(self.nrst_mode == NrstMode::Gpio) << 14)
+            | (u32::from(self.nboot1) << 15)
+    }
+}
+
+fn check_aligned(offset: u32, align: usize) -> Result<(), Error> {
+    if offset as usize % align != 0 {
+        Err(Error::Alignment)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_bounds(offset: u32, len: usize) -> Result<(), Error> {
+    if len > FLASH_SIZE || offset as usize > FLASH_SIZE - len {
+        Err(Error::OutOfBounds)
+    } else {
+        Ok(())
+    }
+}
+
+impl ErrorType for Flash {
+    type Error = Error;
+}
+
+impl ReadNorFlash for Flash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let data = self.read_slice(offset, bytes.len())?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        FLASH_SIZE
+    }
+}
+
+impl NorFlash for Flash {
+    const WRITE_SIZE: usize = PAGE_SIZE;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.unlock().erase_range(from, to)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.unlock().program(offset, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_bytes_decode() {
+        let ob = OptionBytes::from_bits(0xAA | (1 << 8) | (3 << 9) | (1 << 12) | (1 << 15));
+        assert_eq!(
+            ob,
+            OptionBytes {
+                read_protection: ReadProtection::Level0,
+                bor: Some(BorLevel::V2_4),
+                iwdg_sw: true,
+                wwdg_sw: false,
+                nrst_mode: NrstMode::Reset,
+                nboot1: true,
+            }
+        );
+
+        // The bits above the user option bytes are ignored
+        let ob = OptionBytes::from_bits(0xFFFF_0000 | 0x55 | (1 << 13) | (1 << 14));
+        assert_eq!(ob.read_protection, ReadProtection::Level1);
+        assert_eq!(ob.bor, None);
+        assert!(ob.wwdg_sw);
+        assert_eq!(ob.nrst_mode, NrstMode::Gpio);
+    }
+
+    #[test]
+    fn read_protection_level0_only_with_key() {
+        for rdp in 0..=0xFF_u32 {
+            let expected = if rdp == 0xAA {
+                ReadProtection::Level0
+            } else {
+                ReadProtection::Level1
+            };
+            assert_eq!(OptionBytes::from_bits(rdp).read_protection, expected);
+        }
+    }
+
+    #[test]
+    fn option_bytes_round_trip() {
+        for &rdp in &[0xAA, 0x55] {
+            for user in 0..=0xFF_u32 {
+                // The BOR level bits are only kept if the BOR is enabled
+                if user & 1 == 0 && user & 0b1110 != 0 {
+                    continue;
+                }
+                let bits = rdp | (user << 8);
+                assert_eq!(OptionBytes::from_bits(bits).bits(), bits, "{:#x}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn option_bytes_encode() {
+        let ob = OptionBytes {
+            read_protection: ReadProtection::Level1,
+            bor: Some(BorLevel::V3_2),
+            iwdg_sw: false,
+            wwdg_sw: true,
+            nrst_mode: NrstMode::Gpio,
+            nboot1: false,
+        };
+        assert_eq!(
+            ob.bits(),
+            0x55 | (1 << 8) | (7 << 9) | (1 << 13) | (1 << 14)
+        );
+        assert_eq!(OptionBytes::from_bits(ob.bits()), ob);
+    }
+}
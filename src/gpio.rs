@@ -3,8 +3,16 @@
 use core::convert::Infallible;
 use core::marker::PhantomData;
 
+use crate::pac::gpioa::RegisterBlock;
 use crate::rcc::Rcc;
 
+mod dynamic;
+pub use self::dynamic::*;
+mod erased;
+pub use self::erased::*;
+mod group;
+pub use self::group::*;
+
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
     /// The parts to split the GPIO into
@@ -87,6 +95,129 @@ pub struct Output<MODE> {
 /// Push pull output (type state)
 pub struct PushPull;
 
+/// Output speed (slew rate) of a pin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speed {
+    VeryLow = 0b00,
+    Low = 0b01,
+    High = 0b10,
+    VeryHigh = 0b11,
+}
+
+/// Internal pull resistor of a pin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pull {
+    None = 0b00,
+    Up = 0b01,
+    Down = 0b10,
+}
+
+/// Pin with a locked configuration
+///
+/// Mode, output type, speed, pull and alternate function can't be changed until the next
+/// reset. The pin can still be driven and read.
+pub struct Locked<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> Locked<PIN> {
+    /// Returns a reference to the locked pin
+    pub fn pin(&self) -> &PIN {
+        &self.pin
+    }
+}
+
+/// Mode a pin can be switched to with `into_mode` and `with_mode`
+///
+/// These conversions don't take a `CriticalSection` argument, but the Cortex-M0+ has no atomic
+/// read-modify-write, so all interrupts are still masked globally while the port registers are
+/// updated (a few cycles per conversion and again on restore).
+pub trait PinMode {
+    #[doc(hidden)]
+    const MODER: u32;
+    #[doc(hidden)]
+    const OTYPER: Option<bool> = None;
+    #[doc(hidden)]
+    const PUPDR: Option<u32> = None;
+    #[doc(hidden)]
+    const AF: Option<u32> = None;
+}
+
+impl PinMode for Input<Floating> {
+    const MODER: u32 = 0b00;
+    const PUPDR: Option<u32> = Some(0b00);
+}
+
+impl PinMode for Input<PullUp> {
+    const MODER: u32 = 0b00;
+    const PUPDR: Option<u32> = Some(0b01);
+}
+
+impl PinMode for Input<PullDown> {
+    const MODER: u32 = 0b00;
+    const PUPDR: Option<u32> = Some(0b10);
+}
+
+impl PinMode for Analog {
+    const MODER: u32 = 0b11;
+    const PUPDR: Option<u32> = Some(0b00);
+}
+
+impl PinMode for Output<PushPull> {
+    const MODER: u32 = 0b01;
+    const OTYPER: Option<bool> = Some(false);
+    const PUPDR: Option<u32> = Some(0b00);
+}
+
+impl PinMode for Output<OpenDrain> {
+    const MODER: u32 = 0b01;
+    const OTYPER: Option<bool> = Some(true);
+    const PUPDR: Option<u32> = Some(0b00);
+}
+
+macro_rules! af_mode {
+    ($($AF:ident: $af:expr,)+) => {
+        $(
+            impl PinMode for Alternate<$AF> {
+                const MODER: u32 = 0b10;
+                const AF: Option<u32> = Some($af);
+            }
+        )+
+    };
+}
+
+af_mode! {
+    AF0: 0, AF1: 1, AF2: 2, AF3: 3, AF4: 4, AF5: 5, AF6: 6, AF7: 7,
+    AF8: 8, AF9: 9, AF10: 10, AF11: 11, AF12: 12, AF13: 13, AF14: 14, AF15: 15,
+}
+
+/// Pins 0 to 7, their alternate function is selected in AFRL
+#[doc(hidden)]
+pub struct Afrl;
+
+/// Pins 8 to 15, their alternate function is selected in AFRH
+#[doc(hidden)]
+pub struct Afrh;
+
+/// Mode a pin whose alternate function is selected in `AFR` can be switched to
+///
+/// The py32f002b has no AFRH, so its pins above 7 can't use an alternate function other than AF0.
+pub trait PinModeFor<AFR>: PinMode {}
+
+impl<M: PinMode> PinModeFor<Afrl> for M {}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+impl<M: PinMode> PinModeFor<Afrh> for M {}
+
+#[cfg(feature = "py32f002b")]
+impl<PULL> PinModeFor<Afrh> for Input<PULL> where Input<PULL>: PinMode {}
+#[cfg(feature = "py32f002b")]
+impl PinModeFor<Afrh> for Analog {}
+#[cfg(feature = "py32f002b")]
+impl<MODE> PinModeFor<Afrh> for Output<MODE> where Output<MODE>: PinMode {}
+#[cfg(feature = "py32f002b")]
+impl PinModeFor<Afrh> for Alternate<AF0> {}
+
 use embedded_hal::digital::v2::{toggleable, InputPin, OutputPin, StatefulOutputPin};
 
 /// Fully erased pin
@@ -160,6 +291,83 @@ impl<MODE> InputPin for Pin<Input<MODE>> {
     }
 }
 
+impl<PIN: OutputPin> OutputPin for Locked<PIN> {
+    type Error = PIN::Error;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high()
+    }
+
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()
+    }
+}
+
+impl<PIN: StatefulOutputPin> StatefulOutputPin for Locked<PIN> {
+    #[inline(always)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_high()
+    }
+
+    #[inline(always)]
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_set_low()
+    }
+}
+
+impl<PIN: StatefulOutputPin> toggleable::Default for Locked<PIN> {}
+
+impl<PIN: InputPin> InputPin for Locked<PIN> {
+    type Error = PIN::Error;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+/// Returns the registers of a port, all ports share the layout of GPIOA
+fn port_block(port: u8) -> &'static RegisterBlock {
+    let ptr = match port {
+        b'A' => crate::pac::GPIOA::ptr() as *const RegisterBlock,
+        b'B' => crate::pac::GPIOB::ptr() as *const RegisterBlock,
+        #[cfg(feature = "py32f002b")]
+        b'C' => crate::pac::GPIOC::ptr() as *const RegisterBlock,
+        #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+        b'F' => crate::pac::GPIOF::ptr() as *const RegisterBlock,
+        _ => unreachable!(),
+    };
+    // NOTE(unsafe) the pin owning the index is the only one accessing its bits
+    unsafe { &*ptr }
+}
+
+/// Runs the LCKR key sequence for the pins in `mask`
+///
+/// Returns `true` if all of them are locked afterwards. The key sequence only works once per
+/// port, after that LCKR is frozen and pins which weren't part of the first sequence stay
+/// unlocked.
+fn lock_pins(port: u8, mask: u16) -> bool {
+    let lckk = 1 << 16;
+    let pins = u32::from(mask);
+    let reg = port_block(port);
+    // The key sequence must not be interrupted by other accesses to LCKR
+    let lckr = cortex_m::interrupt::free(|_| unsafe {
+        reg.lckr.write(|w| w.bits(lckk | pins));
+        reg.lckr.write(|w| w.bits(pins));
+        reg.lckr.write(|w| w.bits(lckk | pins));
+        reg.lckr.read();
+        reg.lckr.read().bits()
+    });
+    lckr & lckk != 0 && lckr & pins == pins
+}
+
 macro_rules! gpio_trait {
     ($gpiox:ident) => {
         impl GpioRegExt for crate::pac::$gpiox::RegisterBlock {
@@ -194,8 +402,8 @@ gpio_trait!(gpiof);
 gpio_trait!(gpioc);
 
 macro_rules! gpio {
-    ([$($GPIOX:ident, $gpiox:ident, $gpioxenr:ident, $PXx:ident, $gate:meta => [
-        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty),)+
+    ([$($GPIOX:ident, $gpiox:ident, $PXx:ident, $port_id:expr, $gate:meta => [
+        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty, $afr:ident),)+
     ]),+]) => {
         $(
             /// GPIO
@@ -206,15 +414,15 @@ macro_rules! gpio {
 
                 use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, toggleable};
                 use crate::{
-                    rcc::Rcc,
+                    rcc::{Enable, Rcc},
                     pac::$GPIOX
                 };
 
                 use cortex_m::interrupt::CriticalSection;
 
                 use super::{
-                    Alternate, Analog, Floating, GpioExt, Input, OpenDrain, Output,
-                    PullDown, PullUp, PushPull, AF0, AF1, AF2, AF3, AF4, AF5, AF6, AF7,
+                    Alternate, Analog, DynamicPin, ErasedPin, Floating, GpioExt, GroupPin, Input, Locked, OutputGroupPin,
+                    OpenDrain, Output, PinMode, PinModeFor, Pull, PullDown, PullUp, PushPull, Speed, AF0, AF1, AF2, AF3, AF4, AF5, AF6, AF7,
                     AF8, AF9, AF10, AF11, AF12, AF13, AF14, AF15, Pin, GpioRegExt,
                 };
 
@@ -230,7 +438,7 @@ macro_rules! gpio {
                     type Parts = Parts;
 
                     fn split(self, rcc: &mut Rcc) -> Parts {
-                        rcc.regs.iopenr.modify(|_, w| w.$gpioxenr().set_bit());
+                        $GPIOX::enable(rcc);
 
                         Parts {
                             $(
@@ -269,6 +477,112 @@ macro_rules! gpio {
                     }
                 }
 
+                fn _set_speed(index: usize, speed: Speed) {
+                    let offset = 2 * index;
+                    cortex_m::interrupt::free(|_| unsafe {
+                        let reg = &(*$GPIOX::ptr());
+                        reg.ospeedr.modify(|r, w| {
+                            w.bits((r.bits() & !(0b11 << offset)) | ((speed as u32) << offset))
+                        });
+                    });
+                }
+
+                fn _set_pull(index: usize, pull: Pull) {
+                    let offset = 2 * index;
+                    cortex_m::interrupt::free(|_| unsafe {
+                        let reg = &(*$GPIOX::ptr());
+                        reg.pupdr.modify(|r, w| {
+                            w.bits((r.bits() & !(0b11 << offset)) | ((pull as u32) << offset))
+                        });
+                    });
+                }
+
+                /// Register block of the port
+                type Regs = <$GPIOX as core::ops::Deref>::Target;
+
+                fn _set_mode<M: PinMode>(index: usize) {
+                    let offset = 2 * index;
+                    cortex_m::interrupt::free(|_| unsafe {
+                        let reg = &(*$GPIOX::ptr());
+                        if let Some(af) = M::AF {
+                            _write_af(reg, index, af);
+                        }
+                        if let Some(open_drain) = M::OTYPER {
+                            reg.otyper.modify(|r, w| {
+                                w.bits((r.bits() & !(1 << index)) | (u32::from(open_drain) << index))
+                            });
+                        }
+                        if let Some(pull) = M::PUPDR {
+                            reg.pupdr.modify(|r, w| {
+                                w.bits((r.bits() & !(0b11 << offset)) | (pull << offset))
+                            });
+                        }
+                        reg.moder.modify(|r, w| {
+                            w.bits((r.bits() & !(0b11 << offset)) | (M::MODER << offset))
+                        });
+                    });
+                }
+
+                /// Saved moder, otyper, pupdr and alternate function bits of a pin
+                type ModeState = (u32, u32, u32, u32);
+
+                fn _save_mode(index: usize) -> ModeState {
+                    let offset = 2 * index;
+                    let reg = unsafe { &(*$GPIOX::ptr()) };
+                    (
+                        (reg.moder.read().bits() >> offset) & 0b11,
+                        (reg.otyper.read().bits() >> index) & 0b1,
+                        (reg.pupdr.read().bits() >> offset) & 0b11,
+                        _read_af(reg, index),
+                    )
+                }
+
+                fn _read_af(reg: &Regs, index: usize) -> u32 {
+                    if index >= 8 {
+                        #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+                        return (reg.afrh.read().bits() >> (4 * (index - 8))) & 0b1111;
+                        // py32f002b does not have AFRH, its pins above 7 always use AF0
+                        #[cfg(not(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a")))]
+                        return 0;
+                    }
+                    (reg.afrl.read().bits() >> (4 * index)) & 0b1111
+                }
+
+                fn _restore_mode(index: usize, (moder, otyper, pupdr, af): ModeState) {
+                    let offset = 2 * index;
+                    cortex_m::interrupt::free(|_| unsafe {
+                        let reg = &(*$GPIOX::ptr());
+                        _write_af(reg, index, af);
+                        reg.otyper.modify(|r, w| {
+                            w.bits((r.bits() & !(1 << index)) | (otyper << index))
+                        });
+                        reg.pupdr.modify(|r, w| {
+                            w.bits((r.bits() & !(0b11 << offset)) | (pupdr << offset))
+                        });
+                        reg.moder.modify(|r, w| {
+                            w.bits((r.bits() & !(0b11 << offset)) | (moder << offset))
+                        });
+                    });
+                }
+
+                unsafe fn _write_af(reg: &Regs, index: usize, af: u32) {
+                    if index < 8 {
+                        let offset = 4 * index;
+                        reg.afrl.modify(|r, w| {
+                            w.bits((r.bits() & !(0b1111 << offset)) | (af << offset))
+                        });
+                    } else {
+                        #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))] {
+                            let offset = 4 * (index - 8);
+                            reg.afrh.modify(|r, w| {
+                                w.bits((r.bits() & !(0b1111 << offset)) | (af << offset))
+                            });
+                        }
+
+                        // py32f002b does not have AFRH, its pins above 7 always use AF0 (see `PinModeFor`)
+                    }
+                }
+
                 $(
                     /// Pin
                     pub struct $PXi<MODE> {
@@ -527,7 +841,7 @@ macro_rules! gpio {
                                     w.bits(r.bits() & !(0b1 << $i))
                                 });
                                 reg.ospeedr.modify(|r, w| {
-                                    w.bits(r.bits() & !(0b1 << $i))
+                                    w.bits(r.bits() | (0b11 << offset))
                                 });
                                 reg.moder.modify(|r, w| {
                                     w.bits((r.bits() & !(0b11 << offset)) | (0b01 << offset))
@@ -535,6 +849,114 @@ macro_rules! gpio {
                             }
                             $PXi { _mode: PhantomData }
                         }
+
+                        /// Locks the configuration of the pin until the next reset
+                        ///
+                        /// The lock key sequence only takes effect once per port. If another
+                        /// pin of the port was locked before, this pin can't be locked anymore
+                        /// and is returned as error. Use `PinGroup::lock` to lock several pins
+                        /// of a port.
+                        pub fn lock(self) -> Result<Locked<Self>, Self> {
+                            if super::lock_pins($port_id, 1 << $i) {
+                                Ok(Locked { pin: self })
+                            } else {
+                                Err(self)
+                            }
+                        }
+
+                        /// Erases the port and pin number from the type
+                        pub fn erase(self) -> ErasedPin<MODE> {
+                            ErasedPin::new($port_id, $i)
+                        }
+
+                        /// Turns the pin into a floating input whose mode can be changed at runtime
+                        pub fn into_dynamic(self) -> DynamicPin {
+                            DynamicPin::new($port_id, $i)
+                        }
+
+                        /// Configures the pin to operate in mode `M`
+                        ///
+                        /// Unlike the `into_*` methods this doesn't take a `CriticalSection`. It
+                        /// enters one internally, so all interrupts are masked globally while the
+                        /// port registers are updated, not only those sharing the port.
+                        pub fn into_mode<M: PinModeFor<super::$afr>>(self) -> $PXi<M> {
+                            _set_mode::<M>($i);
+                            $PXi { _mode: PhantomData }
+                        }
+
+                        /// Temporarily configures the pin to operate in mode `M` while `f` runs,
+                        /// the current configuration is restored afterwards
+                        ///
+                        /// Interrupts are masked globally while the mode is changed and again
+                        /// while it is restored, but not while `f` runs.
+                        pub fn with_mode<M: PinModeFor<super::$afr>, R>(&mut self, f: impl FnOnce(&mut $PXi<M>) -> R) -> R {
+                            let state = _save_mode($i);
+                            _set_mode::<M>($i);
+                            let result = f(&mut $PXi { _mode: PhantomData });
+                            _restore_mode($i, state);
+                            result
+                        }
+
+                        /// Temporarily configures the pin as an input while `f` runs
+                        pub fn with_input<PULL, R>(&mut self, f: impl FnOnce(&mut $PXi<Input<PULL>>) -> R) -> R
+                        where
+                            Input<PULL>: PinModeFor<super::$afr>,
+                        {
+                            self.with_mode(f)
+                        }
+
+                        /// Temporarily configures the pin as a push pull output while `f` runs
+                        pub fn with_push_pull_output<R>(&mut self, f: impl FnOnce(&mut $PXi<Output<PushPull>>) -> R) -> R {
+                            self.with_mode(f)
+                        }
+
+                        /// Temporarily configures the pin to use an alternate function while `f` runs
+                        pub fn with_alternate<AF, R>(&mut self, f: impl FnOnce(&mut $PXi<Alternate<AF>>) -> R) -> R
+                        where
+                            Alternate<AF>: PinModeFor<super::$afr>,
+                        {
+                            self.with_mode(f)
+                        }
+                    }
+
+                    impl<MODE> GroupPin for $PXi<MODE> {
+                        type Port = $GPIOX;
+                        const PORT: u8 = $port_id;
+                        const MASK: u16 = 1 << $i;
+                    }
+
+                    impl<MODE> OutputGroupPin for $PXi<Output<MODE>> {}
+
+                    impl<MODE> From<$PXi<MODE>> for ErasedPin<MODE> {
+                        fn from(pin: $PXi<MODE>) -> Self {
+                            pin.erase()
+                        }
+                    }
+
+                    impl<MODE> $PXi<Output<MODE>> {
+                        /// Sets the output speed
+                        pub fn set_speed(&mut self, speed: Speed) {
+                            _set_speed($i, speed);
+                        }
+                    }
+
+                    impl $PXi<Output<OpenDrain>> {
+                        /// Sets the internal pull resistor
+                        pub fn set_pull(&mut self, pull: Pull) {
+                            _set_pull($i, pull);
+                        }
+                    }
+
+                    impl<AF> $PXi<Alternate<AF>> {
+                        /// Sets the output speed
+                        pub fn set_speed(&mut self, speed: Speed) {
+                            _set_speed($i, speed);
+                        }
+
+                        /// Sets the internal pull resistor
+                        pub fn set_pull(&mut self, pull: Pull) {
+                            _set_pull($i, pull);
+                        }
                     }
 
                     impl $PXi<Output<OpenDrain>> {
@@ -662,54 +1084,54 @@ macro_rules! gpio {
 }
 
 gpio!([
-    GPIOA, gpioa, gpioaen, PA, any(
+    GPIOA, gpioa, PA, b'A', any(
         feature = "device-selected"
     ) => [
-        PA0: (pa0, 0, Input<Floating>),
-        PA1: (pa1, 1, Input<Floating>),
-        PA2: (pa2, 2, Input<Floating>),
-        PA3: (pa3, 3, Input<Floating>),
-        PA4: (pa4, 4, Input<Floating>),
-        PA5: (pa5, 5, Input<Floating>),
-        PA6: (pa6, 6, Input<Floating>),
-        PA7: (pa7, 7, Input<Floating>),
-        PA8: (pa8, 8, Input<Floating>),
-        PA9: (pa9, 9, Input<Floating>),
-        PA10: (pa10, 10, Input<Floating>),
-        PA11: (pa11, 11, Input<Floating>),
-        PA12: (pa12, 12, Input<Floating>),
-        PA13: (pa13, 13, Input<Floating>),
-        PA14: (pa14, 14, Input<Floating>),
-        PA15: (pa15, 15, Input<Floating>),
+        PA0: (pa0, 0, Input<Floating>, Afrl),
+        PA1: (pa1, 1, Input<Floating>, Afrl),
+        PA2: (pa2, 2, Input<Floating>, Afrl),
+        PA3: (pa3, 3, Input<Floating>, Afrl),
+        PA4: (pa4, 4, Input<Floating>, Afrl),
+        PA5: (pa5, 5, Input<Floating>, Afrl),
+        PA6: (pa6, 6, Input<Floating>, Afrl),
+        PA7: (pa7, 7, Input<Floating>, Afrl),
+        PA8: (pa8, 8, Input<Floating>, Afrh),
+        PA9: (pa9, 9, Input<Floating>, Afrh),
+        PA10: (pa10, 10, Input<Floating>, Afrh),
+        PA11: (pa11, 11, Input<Floating>, Afrh),
+        PA12: (pa12, 12, Input<Floating>, Afrh),
+        PA13: (pa13, 13, Input<Floating>, Afrh),
+        PA14: (pa14, 14, Input<Floating>, Afrh),
+        PA15: (pa15, 15, Input<Floating>, Afrh),
     ],
-    GPIOB, gpiob, gpioben, PB, any(
+    GPIOB, gpiob, PB, b'B', any(
         feature = "device-selected"
     ) => [
-        PB0: (pb0, 0, Input<Floating>),
-        PB1: (pb1, 1, Input<Floating>),
-        PB2: (pb2, 2, Input<Floating>),
-        PB3: (pb3, 3, Input<Floating>),
-        PB4: (pb4, 4, Input<Floating>),
-        PB5: (pb5, 5, Input<Floating>),
-        PB6: (pb6, 6, Input<Floating>),
-        PB7: (pb7, 7, Input<Floating>),
-        PB8: (pb8, 8, Input<Floating>),
+        PB0: (pb0, 0, Input<Floating>, Afrl),
+        PB1: (pb1, 1, Input<Floating>, Afrl),
+        PB2: (pb2, 2, Input<Floating>, Afrl),
+        PB3: (pb3, 3, Input<Floating>, Afrl),
+        PB4: (pb4, 4, Input<Floating>, Afrl),
+        PB5: (pb5, 5, Input<Floating>, Afrl),
+        PB6: (pb6, 6, Input<Floating>, Afrl),
+        PB7: (pb7, 7, Input<Floating>, Afrl),
+        PB8: (pb8, 8, Input<Floating>, Afrh),
     ],
-    GPIOC, gpioc, gpiocen, PC, any(
+    GPIOC, gpioc, PC, b'C', any(
         feature = "py32f002b"
     ) => [
-        PC0: (pf0, 0, Input<Floating>),
-        PC1: (pf1, 1, Input<Floating>),
+        PC0: (pf0, 0, Input<Floating>, Afrl),
+        PC1: (pf1, 1, Input<Floating>, Afrl),
     ],
-    GPIOF, gpiof, gpiofen, PF, any(
+    GPIOF, gpiof, PF, b'F', any(
         feature = "py32f030",
         feature = "py32f003",
         feature = "py32f002a"
     ) => [
-        PF0: (pf0, 0, Input<Floating>),
-        PF1: (pf1, 1, Input<Floating>),
-        PF2: (pf2, 2, Input<Floating>),
-        PF3: (pf3, 3, Input<Floating>),
-        PF4: (pf4, 4, Input<Floating>),
+        PF0: (pf0, 0, Input<Floating>, Afrl),
+        PF1: (pf1, 1, Input<Floating>, Afrl),
+        PF2: (pf2, 2, Input<Floating>, Afrl),
+        PF3: (pf3, 3, Input<Floating>, Afrl),
+        PF4: (pf4, 4, Input<Floating>, Afrl),
     ]
 ]);
@@ -0,0 +1,230 @@
+use super::*;
+
+/// Runtime mode of a [`DynamicPin`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dynamic {
+    InputFloating,
+    InputPullUp,
+    InputPullDown,
+    OutputPushPull,
+    OutputOpenDrain,
+    Analog,
+    /// Alternate function with the given number
+    Alternate(u8),
+}
+
+/// Output driver of a [`DynamicPin`] using an alternate function
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputType {
+    PushPull,
+    /// Required by alternate functions sharing a line, e.g. I2C
+    OpenDrain,
+}
+
+/// Error returned when a [`DynamicPin`] is not in a mode supporting the access
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinModeError {
+    IncorrectMode,
+    /// The alternate function can't be selected for the pin
+    UnsupportedAlternate,
+}
+
+impl Dynamic {
+    fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Dynamic::InputFloating
+                | Dynamic::InputPullUp
+                | Dynamic::InputPullDown
+                | Dynamic::OutputOpenDrain
+        )
+    }
+
+    fn is_output(&self) -> bool {
+        matches!(self, Dynamic::OutputPushPull | Dynamic::OutputOpenDrain)
+    }
+}
+
+/// Pin whose mode can be changed at runtime
+///
+/// Reads and writes are checked against the current mode, which is useful for bidirectional
+/// single wire buses. An open drain output can be read back as well.
+pub struct DynamicPin {
+    port: u8,
+    i: u8,
+    mode: Dynamic,
+}
+
+impl DynamicPin {
+    pub(super) fn new(port: u8, i: u8) -> Self {
+        let mut pin = Self {
+            port,
+            i,
+            mode: Dynamic::InputFloating,
+        };
+        pin.make_floating_input();
+        pin
+    }
+
+    /// Returns the port of the pin, e.g. `'A'`
+    pub fn port_id(&self) -> char {
+        self.port as char
+    }
+
+    /// Returns the number of the pin within its port
+    pub fn pin_id(&self) -> u8 {
+        self.i
+    }
+
+    /// Returns the current mode
+    pub fn mode(&self) -> Dynamic {
+        self.mode
+    }
+
+    /// Configures the pin as a floating input
+    pub fn make_floating_input(&mut self) {
+        self.configure(0b00, false, Pull::None);
+        self.mode = Dynamic::InputFloating;
+    }
+
+    /// Configures the pin as a pulled up input
+    pub fn make_pull_up_input(&mut self) {
+        self.configure(0b00, false, Pull::Up);
+        self.mode = Dynamic::InputPullUp;
+    }
+
+    /// Configures the pin as a pulled down input
+    pub fn make_pull_down_input(&mut self) {
+        self.configure(0b00, false, Pull::Down);
+        self.mode = Dynamic::InputPullDown;
+    }
+
+    /// Configures the pin as a push pull output
+    pub fn make_push_pull_output(&mut self) {
+        self.configure(0b01, false, Pull::None);
+        self.mode = Dynamic::OutputPushPull;
+    }
+
+    /// Configures the pin as an open drain output
+    pub fn make_open_drain_output(&mut self) {
+        self.configure(0b01, true, Pull::None);
+        self.mode = Dynamic::OutputOpenDrain;
+    }
+
+    /// Configures the pin as an analog pin
+    pub fn make_analog(&mut self) {
+        self.configure(0b11, false, Pull::None);
+        self.mode = Dynamic::Analog;
+    }
+
+    /// Configures the pin to use the alternate function `af`
+    ///
+    /// Fails without changing the pin if `af` is above 15. py32f002b has no alternate function
+    /// register for pins above 7, these only support AF0.
+    pub fn make_alternate(
+        &mut self,
+        af: u8,
+        output: OutputType,
+        pull: Pull,
+    ) -> Result<(), PinModeError> {
+        if af > 15 {
+            return Err(PinModeError::UnsupportedAlternate);
+        }
+        #[cfg(not(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a")))]
+        if self.i >= 8 && af != 0 {
+            return Err(PinModeError::UnsupportedAlternate);
+        }
+
+        let bits = u32::from(af);
+        let offset = 4 * u32::from(self.i % 8);
+        let reg = port_block(self.port);
+        cortex_m::interrupt::free(|_| unsafe {
+            if self.i < 8 {
+                reg.afrl
+                    .modify(|r, w| w.bits((r.bits() & !(0b1111 << offset)) | (bits << offset)));
+            } else {
+                #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+                reg.afrh
+                    .modify(|r, w| w.bits((r.bits() & !(0b1111 << offset)) | (bits << offset)));
+            }
+        });
+        self.configure(0b10, output == OutputType::OpenDrain, pull);
+        self.mode = Dynamic::Alternate(af);
+        Ok(())
+    }
+
+    fn configure(&mut self, moder: u32, open_drain: bool, pull: Pull) {
+        let offset = 2 * u32::from(self.i);
+        let reg = port_block(self.port);
+        cortex_m::interrupt::free(|_| unsafe {
+            reg.pupdr
+                .modify(|r, w| w.bits((r.bits() & !(0b11 << offset)) | ((pull as u32) << offset)));
+            reg.otyper.modify(|r, w| {
+                w.bits((r.bits() & !(1 << self.i)) | (u32::from(open_drain) << self.i))
+            });
+            reg.moder
+                .modify(|r, w| w.bits((r.bits() & !(0b11 << offset)) | (moder << offset)));
+        });
+    }
+
+    /// Drives the output high
+    pub fn set_high(&mut self) -> Result<(), PinModeError> {
+        self.set_state(true)
+    }
+
+    /// Drives the output low
+    pub fn set_low(&mut self) -> Result<(), PinModeError> {
+        self.set_state(false)
+    }
+
+    /// Drives the output high (`true`) or low (`false`)
+    pub fn set_state(&mut self, high: bool) -> Result<(), PinModeError> {
+        if !self.mode.is_output() {
+            return Err(PinModeError::IncorrectMode);
+        }
+        let reg = port_block(self.port);
+        if high {
+            reg.set_high(self.i);
+        } else {
+            reg.set_low(self.i);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the input level is high
+    pub fn is_high(&self) -> Result<bool, PinModeError> {
+        self.is_low().map(|v| !v)
+    }
+
+    /// Returns `true` if the input level is low
+    pub fn is_low(&self) -> Result<bool, PinModeError> {
+        if !self.mode.is_input() {
+            return Err(PinModeError::IncorrectMode);
+        }
+        Ok(port_block(self.port).is_low(self.i))
+    }
+}
+
+impl OutputPin for DynamicPin {
+    type Error = PinModeError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_high(self)
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        DynamicPin::set_low(self)
+    }
+}
+
+impl InputPin for DynamicPin {
+    type Error = PinModeError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        DynamicPin::is_high(self)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        DynamicPin::is_low(self)
+    }
+}
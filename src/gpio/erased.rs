@@ -0,0 +1,94 @@
+use super::*;
+
+/// Fully erased pin, remembering its port and pin number at runtime
+///
+/// Unlike [`Pin`], pins of any port and in any mode can be erased, e.g. to collect pins of
+/// different ports into an array.
+pub struct ErasedPin<MODE> {
+    port: u8,
+    i: u8,
+    _mode: PhantomData<MODE>,
+}
+
+impl<MODE> ErasedPin<MODE> {
+    pub(super) fn new(port: u8, i: u8) -> Self {
+        Self {
+            port,
+            i,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Returns the port of the pin, e.g. `'A'`
+    pub fn port_id(&self) -> char {
+        self.port as char
+    }
+
+    /// Returns the number of the pin within its port
+    pub fn pin_id(&self) -> u8 {
+        self.i
+    }
+
+    #[inline(always)]
+    fn block(&self) -> &RegisterBlock {
+        port_block(self.port)
+    }
+}
+
+impl<MODE> StatefulOutputPin for ErasedPin<Output<MODE>> {
+    #[inline(always)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        self.is_set_low().map(|v| !v)
+    }
+
+    #[inline(always)]
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(self.block().is_set_low(self.i))
+    }
+}
+
+impl<MODE> OutputPin for ErasedPin<Output<MODE>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.block().set_high(self.i);
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.block().set_low(self.i);
+        Ok(())
+    }
+}
+
+impl<MODE> toggleable::Default for ErasedPin<Output<MODE>> {}
+
+impl InputPin for ErasedPin<Output<OpenDrain>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.is_low().map(|v| !v)
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(self.block().is_low(self.i))
+    }
+}
+
+impl<MODE> InputPin for ErasedPin<Input<MODE>> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.is_low().map(|v| !v)
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(self.block().is_low(self.i))
+    }
+}
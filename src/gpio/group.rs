@@ -0,0 +1,146 @@
+use super::*;
+
+/// Pin that can be combined with other pins of its port into a [`PinGroup`]
+pub trait GroupPin {
+    /// Port of the pin, pins of different ports can't be grouped
+    type Port;
+    #[doc(hidden)]
+    const PORT: u8;
+    /// Bit of the pin in the port registers
+    const MASK: u16;
+}
+
+/// Pin configured as output, only groups of outputs can be driven
+pub trait OutputGroupPin: GroupPin {}
+
+/// Tuple of pins sharing the same port
+pub trait PinSet {
+    /// Port of the pins
+    type Port;
+    #[doc(hidden)]
+    const PORT: u8;
+    /// Bits of the pins in the port registers
+    const MASK: u16;
+}
+
+/// Tuple of output pins sharing the same port
+pub trait OutputPinSet: PinSet {}
+
+macro_rules! pin_set {
+    ($($P:ident),+) => {
+        impl<P0, $($P),+> PinSet for (P0, $($P),+)
+        where
+            P0: GroupPin,
+            $($P: GroupPin<Port = P0::Port>,)+
+        {
+            type Port = P0::Port;
+            const PORT: u8 = P0::PORT;
+            const MASK: u16 = P0::MASK $(| $P::MASK)+;
+        }
+
+        impl<P0, $($P),+> OutputPinSet for (P0, $($P),+)
+        where
+            P0: OutputGroupPin,
+            $($P: OutputGroupPin<Port = P0::Port>,)+
+        {
+        }
+    };
+}
+
+pin_set!(P1);
+pin_set!(P1, P2);
+pin_set!(P1, P2, P3);
+pin_set!(P1, P2, P3, P4);
+pin_set!(P1, P2, P3, P4, P5);
+pin_set!(P1, P2, P3, P4, P5, P6);
+pin_set!(P1, P2, P3, P4, P5, P6, P7);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13, P14);
+pin_set!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13, P14, P15);
+
+/// Pins of one port accessed together
+///
+/// Values and masks use the bit positions of the port, e.g. bit 3 is `PX3`. All pins of the
+/// group are updated with a single write to BSRR and read with a single read of IDR.
+pub struct PinGroup<PINS> {
+    pins: PINS,
+}
+
+impl<PINS: PinSet> PinGroup<PINS> {
+    /// Groups pins of the same port
+    pub fn new(pins: PINS) -> Self {
+        Self { pins }
+    }
+
+    /// Returns the bits of the grouped pins
+    pub fn mask(&self) -> u16 {
+        PINS::MASK
+    }
+
+    /// Returns the input levels of the grouped pins
+    pub fn read(&self) -> u16 {
+        port_block(PINS::PORT).idr.read().bits() as u16 & PINS::MASK
+    }
+
+    /// Splits the group into its pins
+    pub fn release(self) -> PINS {
+        self.pins
+    }
+
+    /// Locks the configuration of all grouped pins until the next reset
+    ///
+    /// The lock key sequence only takes effect once per port, so all pins of a port which
+    /// should be locked have to be grouped. If a pin of the port was locked before, the group
+    /// is returned as error.
+    pub fn lock(self) -> Result<Locked<Self>, Self> {
+        if lock_pins(PINS::PORT, PINS::MASK) {
+            Ok(Locked { pin: self })
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<PINS: OutputPinSet> PinGroup<PINS> {
+    /// Drives the grouped pins to the levels of `value`
+    pub fn write(&mut self, value: u16) {
+        let set = value & PINS::MASK;
+        let reset = !value & PINS::MASK;
+        self.bsrr(set, reset);
+    }
+
+    /// Drives the grouped pins in `mask` high
+    pub fn set_mask(&mut self, mask: u16) {
+        self.bsrr(mask & PINS::MASK, 0);
+    }
+
+    /// Drives the grouped pins in `mask` low
+    pub fn clear_mask(&mut self, mask: u16) {
+        self.bsrr(0, mask & PINS::MASK);
+    }
+
+    /// Returns the output levels the grouped pins are driven to
+    pub fn read_output(&self) -> u16 {
+        port_block(PINS::PORT).odr.read().bits() as u16 & PINS::MASK
+    }
+
+    fn bsrr(&mut self, set: u16, reset: u16) {
+        let bits = u32::from(set) | (u32::from(reset) << 16);
+        // NOTE(unsafe) atomic write to a stateless register
+        port_block(PINS::PORT)
+            .bsrr
+            .write(|w| unsafe { w.bits(bits) });
+    }
+}
+
+impl<PINS> Locked<PinGroup<PINS>> {
+    /// Returns the locked group, its pins can still be driven and read
+    pub fn group_mut(&mut self) -> &mut PinGroup<PINS> {
+        &mut self.pin
+    }
+}
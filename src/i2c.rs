@@ -4,7 +4,7 @@ use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
 
 use crate::{
     gpio::*,
-    rcc::Rcc,
+    rcc::{ClockDependent, Clocks, Enable, Rcc, Reset},
     time::{Hertz, KiloHertz, U32Ext},
 };
 
@@ -12,11 +12,15 @@ use crate::{
 pub struct I2c<I2C, SCLPIN, SDAPIN> {
     i2c: I2C,
     pins: (SCLPIN, SDAPIN),
+    speed: KiloHertz,
 }
 
 pub trait SclPin<I2C> {}
 pub trait SdaPin<I2C> {}
 
+impl<I2C, PIN: SclPin<I2C>> SclPin<I2C> for Locked<PIN> {}
+impl<I2C, PIN: SdaPin<I2C>> SdaPin<I2C> for Locked<PIN> {}
+
 macro_rules! i2c_pins {
     ($($I2C:ident => {
         scl => [$($scl:ty),+ $(,)*],
@@ -83,7 +87,7 @@ pub enum Error {
 }
 
 macro_rules! i2c {
-    ($($I2C:ident: ($i2c:ident, $i2cXen:ident, $i2cXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($I2C:ident: $i2c:ident,)+) => {
         $(
             use crate::pac::$I2C;
             impl<SCLPIN, SDAPIN> I2c<$I2C, SCLPIN, SDAPIN> {
@@ -93,12 +97,11 @@ macro_rules! i2c {
                     SDAPIN: SdaPin<$I2C>,
                 {
                     // Enable clock for I2C
-                    rcc.regs.$apbenr.modify(|_, w| w.$i2cXen().set_bit());
+                    $I2C::enable(rcc);
 
                     // Reset I2C
-                    rcc.regs.$apbrstr.modify(|_, w| w.$i2cXrst().set_bit());
-                    rcc.regs.$apbrstr.modify(|_, w| w.$i2cXrst().clear_bit());
-                    I2c { i2c, pins }.i2c_init(rcc.clocks.pclk(), speed)
+                    $I2C::reset(rcc);
+                    I2c { i2c, pins, speed }.i2c_init(rcc.clocks.pclk())
                 }
             }
         )+
@@ -106,7 +109,7 @@ macro_rules! i2c {
 }
 
 i2c! {
-    I2C: (i2c, i2cen, i2crst, apbenr1, apbrstr1),
+    I2C: i2c,
 }
 
 // It's s needed for the impls, but rustc doesn't recognize that
@@ -117,7 +120,14 @@ impl<I2C, SCLPIN, SDAPIN> I2c<I2C, SCLPIN, SDAPIN>
 where
     I2C: Deref<Target = I2cRegisterBlock>,
 {
-    fn i2c_init(self, freq: Hertz, speed: KiloHertz) -> Self {
+    fn i2c_init(self, freq: Hertz) -> Self {
+        self.set_timing(freq);
+        self
+    }
+
+    fn set_timing(&self, freq: Hertz) {
+        let speed = self.speed;
+
         // Make sure the I2C unit is disabled so we can configure it
         self.i2c.cr1.modify(|_, w| w.pe().clear_bit());
 
@@ -148,11 +158,14 @@ where
 
         // Enable the I2C processing
         self.i2c.cr1.modify(|_, w| w.pe().set_bit());
-
-        self
     }
 
-    pub fn release(self) -> (I2C, (SCLPIN, SDAPIN)) {
+    /// Stops the I2C clock and releases the peripheral and pins
+    pub fn release(self) -> (I2C, (SCLPIN, SDAPIN))
+    where
+        I2C: Enable,
+    {
+        unsafe { I2C::disable_unchecked() };
         (self.i2c, self.pins)
     }
 
@@ -217,6 +230,15 @@ where
     }
 }
 
+impl<I2C, SCLPIN, SDAPIN> ClockDependent for I2c<I2C, SCLPIN, SDAPIN>
+where
+    I2C: Deref<Target = I2cRegisterBlock>,
+{
+    fn update_clocks(&mut self, clocks: &Clocks) {
+        self.set_timing(clocks.pclk());
+    }
+}
+
 impl<I2C, SCLPIN, SDAPIN> WriteRead for I2c<I2C, SCLPIN, SDAPIN>
 where
     I2C: Deref<Target = I2cRegisterBlock>,
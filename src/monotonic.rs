@@ -0,0 +1,222 @@
+//! Monotonic clock based on a 16 bit timer
+//!
+//! The timer counts freely with the frequency `FREQ`, its overflows are counted
+//! in the update interrupt, extending the time to 64 bits. Instants and
+//! durations are `fugit` types, so they convert to and from the units of time
+//! with `fugit::ExtU32` (e.g. `10.millis()`).
+//!
+//! With the `rtic` feature the timer implements `rtic_monotonic::Monotonic` and
+//! can be used to schedule RTIC tasks, using compare channel 1 for the timer queue.
+//! TIM1 is not supported with RTIC, its update and compare events raise separate
+//! interrupts while RTIC binds the monotonic to a single one.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac::{self, interrupt};
+//! use crate::hal::prelude::*;
+//! use crate::hal::monotonic::MonoTimer;
+//!
+//! use core::cell::RefCell;
+//! use cortex_m::interrupt::Mutex;
+//!
+//! // Count microseconds with TIM16
+//! static MONO: Mutex<RefCell<Option<MonoTimer<pac::TIM16, 1_000_000>>>> =
+//!     Mutex::new(RefCell::new(None));
+//!
+//! #[interrupt]
+//! fn TIM16() {
+//!     cortex_m::interrupt::free(|cs| {
+//!         if let Some(mono) = MONO.borrow(cs).borrow_mut().as_mut() {
+//!             mono.on_interrupt();
+//!         }
+//!     });
+//! }
+//!
+//! let mut p = pac::Peripherals::take().unwrap();
+//! let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut p.FLASH).unwrap();
+//!
+//! let mono = MonoTimer::tim16(p.TIM16, &mut rcc).unwrap();
+//! cortex_m::interrupt::free(|cs| *MONO.borrow(cs).borrow_mut() = Some(mono));
+//! unsafe { cortex_m::peripheral::NVIC::unmask(pac::Interrupt::TIM16) };
+//!
+//! let start = cortex_m::interrupt::free(|cs| MONO.borrow(cs).borrow_mut().as_mut().unwrap().now());
+//! // ...
+//! let end = cortex_m::interrupt::free(|cs| MONO.borrow(cs).borrow_mut().as_mut().unwrap().now());
+//! let elapsed = (end - start).to_micros();
+//! ```
+
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::time::Hertz;
+use crate::timers::{compute_psc, Error};
+
+/// A point in time with a resolution of `FREQ`
+pub type Instant<const FREQ: u32> = fugit::TimerInstantU64<FREQ>;
+
+/// A span of time with a resolution of `FREQ`
+pub type Duration<const FREQ: u32> = fugit::TimerDurationU64<FREQ>;
+
+/// Monotonic clock counting with the frequency `FREQ`
+pub struct MonoTimer<TIM, const FREQ: u32> {
+    tim: TIM,
+    overflows: u64,
+}
+
+macro_rules! monotonic {
+    ($($TIM:ident: $tim:ident,)+) => {
+        $(
+            use crate::pac::$TIM;
+
+            impl<const FREQ: u32> MonoTimer<$TIM, FREQ> {
+                /// Configures a TIM peripheral as monotonic clock
+                ///
+                /// `FREQ` has to divide the timer clock and must not be below the timer clock
+                /// divided by 65536. The update interrupt of the timer is enabled, its handler
+                /// has to call `on_interrupt`.
+                pub fn $tim(tim: $TIM, rcc: &mut Rcc) -> Result<Self, Error> {
+                    let tclk = rcc.clocks.timer_clk();
+                    if FREQ == 0 || tclk.0 % FREQ != 0 {
+                        return Err(Error::FrequencyOutOfRange);
+                    }
+                    let psc = compute_psc(tclk, Hertz(FREQ))?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+
+                    // Load the prescaler without counting an overflow
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+
+                    tim.dier.modify(|_, w| w.uie().set_bit());
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Ok(MonoTimer { tim, overflows: 0 })
+                }
+
+                /// Returns the current time
+                pub fn now(&mut self) -> Instant<FREQ> {
+                    cortex_m::interrupt::free(|_| {
+                        let cnt = self.tim.cnt.read().bits() as u16;
+                        // The counter wrapped but the overflow interrupt did not run yet
+                        let pending = self.tim.sr.read().uif().bit_is_set() && cnt < 0x8000;
+                        let overflows = self.overflows + u64::from(pending);
+                        Instant::from_ticks((overflows << 16) | u64::from(cnt))
+                    })
+                }
+
+                /// Counts an overflow of the timer, call from the timer interrupt handler
+                pub fn on_interrupt(&mut self) {
+                    if self.tim.sr.read().uif().bit_is_set() {
+                        self.tim.sr.modify(|_, w| w.uif().clear_bit());
+                        self.overflows += 1;
+                    }
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIM {
+                    // Pause counter and disable the interrupts
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.dier.reset();
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    self.tim
+                }
+            }
+        )+
+    };
+}
+
+// The RTIC scheduler binds the monotonic to a single interrupt. Update and compare
+// events of TIM1 are on separate interrupts, so TIM1 can't be used with RTIC.
+#[cfg(feature = "rtic")]
+macro_rules! rtic_monotonic {
+    ($($TIM:ident,)+) => {
+        $(
+            impl<const FREQ: u32> rtic_monotonic::Monotonic for MonoTimer<$TIM, FREQ> {
+                // The overflow interrupt keeps the time running
+                const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+                type Instant = Instant<FREQ>;
+                type Duration = Duration<FREQ>;
+
+                fn now(&mut self) -> Self::Instant {
+                    MonoTimer::now(self)
+                }
+
+                fn set_compare(&mut self, instant: Self::Instant) {
+                    // Instants further away than one overflow cause spurious interrupts,
+                    // after which the scheduler sets the compare value again
+                    self.tim
+                        .ccr1
+                        .write(|w| unsafe { w.ccr().bits(instant.ticks() as u16) });
+                    self.tim.dier.modify(|_, w| w.cc1ie().set_bit());
+                }
+
+                fn clear_compare_flag(&mut self) {
+                    self.tim.sr.modify(|_, w| w.cc1if().clear_bit());
+                }
+
+                fn zero() -> Self::Instant {
+                    Instant::from_ticks(0)
+                }
+
+                unsafe fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                    self.tim.sr.modify(|_, w| w.uif().clear_bit());
+                    self.overflows = 0;
+                }
+
+                fn on_interrupt(&mut self) {
+                    MonoTimer::on_interrupt(self);
+                }
+            }
+        )+
+    };
+}
+
+monotonic! {
+    TIM1: tim1,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+monotonic! {
+    TIM16: tim16,
+}
+
+#[cfg(all(
+    feature = "rtic",
+    any(feature = "py32f030", feature = "py32f003", feature = "py32f002a")
+))]
+rtic_monotonic! {
+    TIM16,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+monotonic! {
+    TIM14: tim14,
+}
+
+#[cfg(all(
+    feature = "rtic",
+    any(feature = "py32f030", feature = "py32f003", feature = "py32f002b")
+))]
+rtic_monotonic! {
+    TIM14,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+monotonic! {
+    TIM3: tim3,
+    TIM17: tim17,
+}
+
+#[cfg(all(feature = "rtic", any(feature = "py32f030", feature = "py32f003")))]
+rtic_monotonic! {
+    TIM3,
+    TIM17,
+}
@@ -0,0 +1,306 @@
+//! API for single pulse generation with the timers in one-pulse mode
+//!
+//! After a trigger the channel 1 output stays inactive for the configured delay,
+//! then becomes active for the configured pulse width and the timer stops. The
+//! trigger is either issued by software or, on TIM1 and TIM3, by an edge on
+//! the channel 2 input.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::capture::Polarity;
+//! use crate::hal::opm::{Config, OnePulse};
+//!
+//! cortex_m::interrupt::free(|cs| {
+//!     let mut p = pac::Peripherals::take().unwrap();
+//!     let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut p.FLASH).unwrap();
+//!
+//!     let gpioa = p.GPIOA.split(&mut rcc);
+//!     let out = gpioa.pa6.into_alternate_af1(cs);
+//!     let trigger = gpioa.pa7.into_alternate_af1(cs);
+//!
+//!     // Fire a 100us pulse 2ms after each rising edge on PA7
+//!     let config = Config::new(2.ms(), 100.us());
+//!     let mut opm = OnePulse::tim3_triggered(p.TIM3, (out, trigger), Polarity::Rising, config, &mut rcc)
+//!         .unwrap();
+//!
+//!     // Or trigger it from software
+//!     opm.trigger();
+//! });
+//! ```
+
+use crate::capture::Polarity;
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::time::{Hertz, MicroSeconds};
+use crate::timers::{Error, PinC1, PinC2};
+
+use void::Void;
+
+/// Timer in one-pulse mode
+pub struct OnePulse<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    clk: Hertz,
+}
+
+/// Output level during the pulse
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputPolarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Pulse configuration
+#[derive(Clone, Copy)]
+pub struct Config {
+    delay: MicroSeconds,
+    width: MicroSeconds,
+    polarity: OutputPolarity,
+}
+
+impl Config {
+    /// Creates a configuration for a pulse of `width` starting `delay` after the trigger
+    pub fn new<D, W>(delay: D, width: W) -> Self
+    where
+        D: Into<MicroSeconds>,
+        W: Into<MicroSeconds>,
+    {
+        Self {
+            delay: delay.into(),
+            width: width.into(),
+            polarity: OutputPolarity::ActiveHigh,
+        }
+    }
+
+    pub fn polarity(mut self, polarity: OutputPolarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+}
+
+/// Computes prescaler, auto-reload and compare values of a pulse
+fn pulse_timing(clk: Hertz, config: &Config) -> Result<(u16, u16, u16), Error> {
+    // The output has to start inactive, so delay and width last at least one tick
+    let ticks = |us: MicroSeconds| (u64::from(clk.0) * u64::from(us.0) / 1_000_000).max(1);
+
+    let total = ticks(config.delay) + ticks(config.width);
+    let psc = cast::u16((total - 1) / (1 << 16)).map_err(|_| Error::FrequencyOutOfRange)?;
+
+    let delay = (ticks(config.delay) / (u64::from(psc) + 1)).max(1);
+    let width = (ticks(config.width) / (u64::from(psc) + 1)).max(1);
+    let arr = cast::u16(delay + width - 1).map_err(|_| Error::FrequencyOutOfRange)?;
+    Ok((psc, arr, delay as u16))
+}
+
+// the following timer have a main output switch which has to be enabled
+macro_rules! moe {
+    (TIM3, $tim:expr) => {};
+    ($_other:ident, $tim:expr) => {
+        $tim.bdtr.modify(|_, w| w.moe().set_bit());
+    };
+}
+
+macro_rules! opm {
+    ($($TIM:ident: $tim:ident,)+) => {
+        $(
+            impl<PIN> OnePulse<$TIM, PIN>
+            where
+                PIN: PinC1<$TIM>,
+            {
+                /// Configures a TIM peripheral to generate pulses on `pin` when triggered by software
+                pub fn $tim(tim: $TIM, pin: PIN, config: Config, rcc: &mut Rcc) -> Result<Self, Error> {
+                    Self::new(tim, pin, config, rcc)
+                }
+            }
+
+            impl<PINS> OnePulse<$TIM, PINS> {
+                fn new(tim: $TIM, pins: PINS, config: Config, rcc: &mut Rcc) -> Result<Self, Error> {
+                    let tclk = rcc.clocks.timer_clk();
+                    let timing = pulse_timing(tclk, &config)?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    // PWM mode 2, the output is active from CCR1 up to ARR
+                    tim.ccmr1_output()
+                        .modify(|_, w| unsafe { w.oc1pe().set_bit().oc1m().bits(0b111) });
+                    moe!($TIM, tim);
+
+                    let mut opm = OnePulse {
+                        tim,
+                        pins,
+                        clk: tclk,
+                    };
+                    opm.load_pulse(timing, config.polarity);
+
+                    // Stop the counter at the next update event
+                    opm.tim.cr1.modify(|_, w| w.opm().set_bit());
+                    Ok(opm)
+                }
+
+                /// Changes delay, width and polarity of the pulse
+                ///
+                /// Delay and width together must not exceed 65536 periods of the timer clock
+                /// divided by 65536, otherwise the pulse is left unchanged and an error is
+                /// returned. Must not be called while a pulse is generated.
+                pub fn set_pulse(&mut self, config: Config) -> Result<(), Error> {
+                    let timing = pulse_timing(self.clk, &config)?;
+                    self.load_pulse(timing, config.polarity);
+                    Ok(())
+                }
+
+                fn load_pulse(&mut self, (psc, arr, delay): (u16, u16, u16), polarity: OutputPolarity) {
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.arr.write(|w| unsafe { w.bits(u32::from(arr)) });
+                    self.tim.ccr1.write(|w| unsafe { w.ccr().bits(delay) });
+                    self.tim.ccer.modify(|_, w| {
+                        w.cc1p()
+                            .bit(polarity == OutputPolarity::ActiveLow)
+                            .cc1e()
+                            .set_bit()
+                    });
+
+                    // Trigger update event to load the registers
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+                }
+
+                /// Starts a pulse
+                pub fn trigger(&mut self) {
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Returns `true` while a pulse is generated
+                pub fn is_busy(&self) -> bool {
+                    self.tim.cr1.read().cen().bit_is_set()
+                }
+
+                /// Waits for the end of the current pulse
+                pub fn wait(&self) -> nb::Result<(), Void> {
+                    if self.is_busy() {
+                        Err(nb::Error::WouldBlock)
+                    } else {
+                        Ok(())
+                    }
+                }
+
+                /// Disables the timer and releases the TIM peripheral and the pins
+                pub fn release(self) -> ($TIM, PINS) {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    (self.tim, self.pins)
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! opm_triggered {
+    ($($TIM:ident: $tim_triggered:ident,)+) => {
+        $(
+            impl<PIN, TRIG> OnePulse<$TIM, (PIN, TRIG)>
+            where
+                PIN: PinC1<$TIM>,
+                TRIG: PinC2<$TIM>,
+            {
+                /// Configures a TIM peripheral to generate pulses on `pin` triggered by `edge` on the channel 2 input
+                ///
+                /// The pulse can still be triggered by software as well.
+                pub fn $tim_triggered(
+                    tim: $TIM,
+                    pins: (PIN, TRIG),
+                    edge: Polarity,
+                    config: Config,
+                    rcc: &mut Rcc,
+                ) -> Result<Self, Error> {
+                    let opm = Self::new(tim, pins, config, rcc)?;
+
+                    opm.tim.ccmr1_input().modify(|_, w| unsafe { w.cc2s().bits(0b01) });
+                    let (p, np) = match edge {
+                        Polarity::Rising => (false, false),
+                        Polarity::Falling => (true, false),
+                        Polarity::Both => (true, true),
+                    };
+                    opm.tim.ccer.modify(|_, w| w.cc2p().bit(p).cc2np().bit(np));
+
+                    // Start the counter on TI2FP2
+                    opm.tim.smcr.modify(|_, w| unsafe { w.ts().bits(0b110).sms().bits(0b110) });
+                    Ok(opm)
+                }
+            }
+        )+
+    };
+}
+
+use crate::pac::TIM1;
+
+opm! {
+    TIM1: tim1,
+}
+
+opm_triggered! {
+    TIM1: tim1_triggered,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+use crate::pac::{TIM16, TIM17, TIM3};
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+opm! {
+    TIM3: tim3,
+    TIM16: tim16,
+    TIM17: tim17,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+opm_triggered! {
+    TIM3: tim3_triggered,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(clk: u32, delay: u32, width: u32) -> Result<(u16, u16, u16), Error> {
+        pulse_timing(
+            Hertz(clk),
+            &Config::new(MicroSeconds(delay), MicroSeconds(width)),
+        )
+    }
+
+    #[test]
+    fn exact_fit() {
+        assert_eq!(timing(48_000_000, 10, 20), Ok((0, 1439, 480)));
+        // Fills the whole counter with a prescaler of 2
+        assert_eq!(timing(1_000_000, 65_536, 65_536), Ok((1, 0xFFFF, 32_768)));
+        // The longest pulse at 48 MHz
+        assert_eq!(timing(48_000_000, 0, 89_478_485), Ok((0xFFFF, 0xFFFF, 1)));
+    }
+
+    #[test]
+    fn rounds_down_to_prescaled_ticks() {
+        assert_eq!(timing(1_000_000, 65_537, 3), Ok((1, 32_768, 32_768)));
+        // Delay and width last at least one tick
+        assert_eq!(timing(1_000_000, 0, 0), Ok((0, 1, 1)));
+        assert_eq!(timing(1_000_000, 0, 65_536), Ok((1, 32_768, 1)));
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(
+            timing(48_000_000, 0, 89_478_486),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            timing(48_000_000, u32::MAX, u32::MAX),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+}
@@ -12,5 +12,9 @@ pub use embedded_hal::digital::v2::StatefulOutputPin as _embedded_hal_gpio_State
 pub use embedded_hal::digital::v2::ToggleableOutputPin as _embedded_hal_gpio_ToggleableOutputPin;
 
 pub use crate::gpio::GpioExt as _py32f0xx_hal_gpio_GpioExt;
+pub use crate::rcc::ClockDependent as _py32f0xx_hal_rcc_ClockDependent;
+pub use crate::rcc::Enable as _py32f0xx_hal_rcc_Enable;
+pub use crate::rcc::GetBusFreq as _py32f0xx_hal_rcc_GetBusFreq;
 pub use crate::rcc::RccExt as _py32f0xx_hal_rcc_RccExt;
+pub use crate::rcc::Reset as _py32f0xx_hal_rcc_Reset;
 pub use crate::time::U32Ext as _py32f0xx_hal_time_U32Ext;
@@ -1,9 +1,10 @@
-use cast::{u16, u32};
+use cast::u32;
 use core::{marker::PhantomData, mem::MaybeUninit};
 
-use crate::rcc::Rcc;
+use crate::rcc::{Enable, Rcc, Reset};
 
 use crate::time::Hertz;
+use crate::timers::{compute_psc_arr, Error};
 use embedded_hal as hal;
 
 pub trait Pins<TIM, P> {
@@ -16,6 +17,7 @@ pub trait Pins<TIM, P> {
     const C4: bool = false;
     type Channels;
 }
+use crate::timers::BkinPin;
 use crate::timers::PinC1;
 use crate::timers::PinC1N;
 use crate::timers::PinC2;
@@ -37,6 +39,128 @@ pub struct PwmChannels<TIM, CHANNELS> {
     _tim: PhantomData<TIM>,
 }
 
+/// PWM timer handle, controls the settings shared by all channels
+pub struct Pwm<TIM> {
+    pub(crate) tim: TIM,
+    clk: Hertz,
+}
+
+/// Output channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    C1,
+    C1N,
+    C2,
+    C2N,
+    C3,
+    C3N,
+    C4,
+}
+
+/// Counting mode of a PWM timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountMode {
+    /// Edge-aligned, counting up
+    Up,
+    /// Edge-aligned, counting down
+    Down,
+    /// Center-aligned, compare flags are set while counting down
+    CenterAligned1,
+    /// Center-aligned, compare flags are set while counting up
+    CenterAligned2,
+    /// Center-aligned, compare flags are set while counting up and down
+    CenterAligned3,
+}
+
+/// Active level of an output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Output level while the main output is disabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleState {
+    Reset,
+    Set,
+}
+
+/// Active level of the break input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakPolarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// Write protection of the timer configuration
+///
+/// The lock level can only be written once after a reset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockLevel {
+    /// No write protection
+    Off = 0,
+    /// Dead time, break and `OISx` settings are locked
+    Level1 = 1,
+    /// Level 1 plus polarity and off-state selection bits
+    Level2 = 2,
+    /// Level 2 plus output compare mode and preload bits
+    Level3 = 3,
+}
+
+/// Encodes a dead time of `ticks` DTS clock cycles for the `DTG` field, rounding down
+fn dead_time_generator(ticks: u16) -> u8 {
+    match ticks {
+        0..=127 => ticks as u8,
+        128..=255 => 0b1000_0000 | (ticks / 2 - 64) as u8,
+        256..=511 => 0b1100_0000 | (ticks / 8 - 32) as u8,
+        _ => 0b1110_0000 | (ticks.min(1023) / 16 - 32) as u8,
+    }
+}
+
+/// Decodes the `DTG` field into DTS clock cycles
+fn dead_time_ticks(dtg: u8) -> u32 {
+    let dtg = u32::from(dtg);
+    match dtg >> 5 {
+        0b000..=0b011 => dtg,
+        0b100 | 0b101 => (64 + (dtg & 0x3F)) * 2,
+        0b110 => (32 + (dtg & 0x1F)) * 8,
+        _ => (32 + (dtg & 0x1F)) * 16,
+    }
+}
+
+/// Returns the PWM frequency for the given prescaler and auto-reload values
+///
+/// A center-aligned period lasts `2 * ARR` ticks, the timer is stopped with ARR = 0
+/// and `Hertz(0)` is returned.
+fn pwm_frequency(tclk: Hertz, psc: u16, arr: u16, center_aligned: bool) -> Hertz {
+    let period = if center_aligned {
+        2 * u32(arr)
+    } else {
+        u32(arr) + 1
+    };
+    Hertz((tclk.0 / (u32(psc) + 1)).checked_div(period).unwrap_or(0))
+}
+
+/// Computes prescaler and auto-reload values for a center-aligned PWM frequency of `freq`
+///
+/// The counter counts up to ARR and back down, so one period lasts `2 * ARR` ticks.
+fn compute_psc_arr_center(tclk: Hertz, freq: Hertz) -> Result<(u16, u16), Error> {
+    let half = freq
+        .0
+        .checked_mul(2)
+        .and_then(|f| tclk.0.checked_div(f))
+        .unwrap_or(0);
+    if half < 1 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+
+    let psc = cast::u16((half - 1) / 0xFFFF).map_err(|_| Error::FrequencyOutOfRange)?;
+    // Never exceeds 0xFFFF as the prescaler is rounded up
+    let arr = half / (u32::from(psc) + 1);
+    Ok((psc, arr as u16))
+}
+
 macro_rules! pins_impl {
     ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),* ); )+ ) => {
         $(
@@ -125,69 +249,85 @@ macro_rules! brk {
 // Timer with four output channels 16 Bit Timer
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
 macro_rules! pwm_4_channels {
-    ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIMX:ident: $timX:ident,)+) => {
         $(
-            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> PINS::Channels
+            pub fn $timX<P, PINS, T>(tim: $TIMX, pins: PINS, rcc: &mut Rcc, freq: T) -> Result<PINS::Channels, Error>
             where
                 PINS: Pins<$TIMX, P>,
                 T: Into<Hertz>,
             {
-                // enable and reset peripheral to a clean slate state
-                rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                Pwm::$timX(tim, pins, rcc, freq).map(|(_, channels)| channels)
+            }
 
-                if PINS::C1 {
-                    tim.ccmr1_output()
-                        .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
-                }
-                if PINS::C2 {
-                    tim.ccmr1_output()
-                        .modify(|_, w| w.oc2pe().set_bit().oc2m().pwm_mode1() );
+            impl Pwm<$TIMX> {
+                /// Configures a TIM peripheral for PWM output, returning the timer handle and the channels
+                pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> Result<(Self, PINS::Channels), Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk();
+                    let (psc, arr) = compute_psc_arr(tclk, freq.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIMX::enable(rcc);
+                    $TIMX::reset(rcc);
+
+                    if PINS::C1 {
+                        tim.ccmr1_output()
+                            .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
+                    }
+                    if PINS::C2 {
+                        tim.ccmr1_output()
+                            .modify(|_, w| w.oc2pe().set_bit().oc2m().pwm_mode1() );
+                    }
+                    if PINS::C3 {
+                        tim.ccmr2_output()
+                            .modify(|_, w| w.oc3pe().set_bit().oc3m().pwm_mode1() );
+                    }
+                    if PINS::C4 {
+                        tim.ccmr2_output()
+                            .modify(|_, w| w.oc4pe().set_bit().oc4m().pwm_mode1() );
+                    }
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+
+                    // enable auto-reload preload
+                    tim.cr1.modify(|_, w| w.arpe().set_bit());
+
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    brk!($TIMX, tim);
+                    tim.cr1.write(|w|
+                        w.cms()
+                            .bits(0b00)
+                            .dir()
+                            .clear_bit()
+                            .opm()
+                            .clear_bit()
+                            .cen()
+                            .set_bit()
+                    );
+                    let pwm = Pwm {
+                        tim,
+                        clk: tclk,
+                    };
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok((pwm, unsafe { MaybeUninit::uninit().assume_init() }))
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIMX {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIMX::disable_unchecked() };
+                    self.tim
                 }
-                if PINS::C3 {
-                    tim.ccmr2_output()
-                        .modify(|_, w| w.oc3pe().set_bit().oc3m().pwm_mode1() );
-                }
-                if PINS::C4 {
-                    tim.ccmr2_output()
-                        .modify(|_, w| w.oc4pe().set_bit().oc4m().pwm_mode1() );
-                }
-
-                // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                let tclk = if rcc.clocks.hclk().0 == rcc.clocks.pclk().0 {
-                    rcc.clocks.pclk().0
-                } else {
-                    rcc.clocks.pclk().0 * 2
-                };
-                let ticks = tclk / freq.into().0;
-
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                tim.psc.write(|w| unsafe { w.psc().bits(psc) });
-                let arr = u16(ticks / u32(psc + 1)).unwrap();
-                tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
-
-                // enable auto-reload preload
-                tim.cr1.modify(|_, w| w.arpe().set_bit());
-
-                // Trigger update event to load the registers
-                tim.cr1.modify(|_, w| w.urs().set_bit());
-                tim.egr.write(|w| w.ug().set_bit());
-                tim.cr1.modify(|_, w| w.urs().clear_bit());
-
-                brk!($TIMX, tim);
-                tim.cr1.write(|w|
-                    w.cms()
-                        .bits(0b00)
-                        .dir()
-                        .clear_bit()
-                        .opm()
-                        .clear_bit()
-                        .cen()
-                        .set_bit()
-                );
-                //NOTE(unsafe) `PINS::Channels` is a ZST
-                unsafe { MaybeUninit::uninit().assume_init() }
             }
 
             impl hal::PwmPin for PwmChannels<$TIMX, C1> {
@@ -311,72 +451,88 @@ macro_rules! pwm_4_channels {
 
 // Timer with four output channels three with complements 16 Bit Timer
 macro_rules! pwm_4_channels_with_3_complementary_outputs {
-    ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIMX:ident: $timX:ident,)+) => {
         $(
-            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> PINS::Channels
+            pub fn $timX<P, PINS, T>(tim: $TIMX, pins: PINS, rcc: &mut Rcc, freq: T) -> Result<PINS::Channels, Error>
             where
                 PINS: Pins<$TIMX, P>,
                 T: Into<Hertz>,
             {
-                // enable and reset peripheral to a clean slate state
-                rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                Pwm::$timX(tim, pins, rcc, freq).map(|(_, channels)| channels)
+            }
 
-                if PINS::C1N | PINS::C1N | PINS::C1N {
-                    tim.bdtr.modify(|_, w| w.ossr().set_bit());
-                }
-                if PINS::C1 {
-                    tim.ccmr1_output()
-                        .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
-                }
-                if PINS::C2 {
-                    tim.ccmr1_output()
-                        .modify(|_, w| w.oc2pe().set_bit().oc2m().pwm_mode1() );
+            impl Pwm<$TIMX> {
+                /// Configures a TIM peripheral for PWM output, returning the timer handle and the channels
+                pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> Result<(Self, PINS::Channels), Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk();
+                    let (psc, arr) = compute_psc_arr(tclk, freq.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIMX::enable(rcc);
+                    $TIMX::reset(rcc);
+
+                    if PINS::C1N | PINS::C2N | PINS::C3N {
+                        tim.bdtr.modify(|_, w| w.ossr().set_bit());
+                    }
+                    if PINS::C1 {
+                        tim.ccmr1_output()
+                            .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
+                    }
+                    if PINS::C2 {
+                        tim.ccmr1_output()
+                            .modify(|_, w| w.oc2pe().set_bit().oc2m().pwm_mode1() );
+                    }
+                    if PINS::C3 {
+                        tim.ccmr2_output()
+                            .modify(|_, w| w.oc3pe().set_bit().oc3m().pwm_mode1() );
+                    }
+                    if PINS::C4 {
+                        tim.ccmr2_output()
+                            .modify(|_, w| w.oc4pe().set_bit().oc4m().pwm_mode1() );
+                    }
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+
+                    // enable auto-reload preload
+                    tim.cr1.modify(|_, w| w.arpe().set_bit());
+
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    brk!($TIMX, tim);
+                    tim.cr1.write(|w|
+                        w.cms()
+                            .bits(0b00)
+                            .dir()
+                            .clear_bit()
+                            .opm()
+                            .clear_bit()
+                            .cen()
+                            .set_bit()
+                    );
+                    let pwm = Pwm {
+                        tim,
+                        clk: tclk,
+                    };
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok((pwm, unsafe { MaybeUninit::uninit().assume_init() }))
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIMX {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIMX::disable_unchecked() };
+                    self.tim
                 }
-                if PINS::C3 {
-                    tim.ccmr2_output()
-                        .modify(|_, w| w.oc3pe().set_bit().oc3m().pwm_mode1() );
-                }
-                if PINS::C4 {
-                    tim.ccmr2_output()
-                        .modify(|_, w| w.oc4pe().set_bit().oc4m().pwm_mode1() );
-                }
-
-                // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                let tclk = if rcc.clocks.hclk().0 == rcc.clocks.pclk().0 {
-                    rcc.clocks.pclk().0
-                } else {
-                    rcc.clocks.pclk().0 * 2
-                };
-                let ticks = tclk / freq.into().0;
-
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                tim.psc.write(|w| unsafe { w.psc().bits(psc) });
-                let arr = u16(ticks / u32(psc + 1)).unwrap();
-                tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
-
-                // enable auto-reload preload
-                tim.cr1.modify(|_, w| w.arpe().set_bit());
-
-                // Trigger update event to load the registers
-                tim.cr1.modify(|_, w| w.urs().set_bit());
-                tim.egr.write(|w| w.ug().set_bit());
-                tim.cr1.modify(|_, w| w.urs().clear_bit());
-
-                brk!($TIMX, tim);
-                tim.cr1.write(|w|
-                    w.cms()
-                        .bits(0b00)
-                        .dir()
-                        .clear_bit()
-                        .opm()
-                        .clear_bit()
-                        .cen()
-                        .set_bit()
-                );
-                //NOTE(unsafe) `PINS::Channels` is a ZST
-                unsafe { MaybeUninit::uninit().assume_init() }
             }
 
             impl hal::PwmPin for PwmChannels<$TIMX, C1> {
@@ -587,17 +743,19 @@ macro_rules! pwm_4_channels_with_3_complementary_outputs {
 
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
 macro_rules! pwm_2_channels {
-    ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIMX:ident: $timX:ident,)+) => {
         $(
-            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> PINS::Channels
+            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> Result<PINS::Channels, Error>
             where
                 PINS: Pins<$TIMX, P>,
                 T: Into<Hertz>,
             {
+                let tclk = rcc.clocks.timer_clk();
+                let (psc, arr) = compute_psc_arr(tclk, freq.into())?;
+
                 // enable and reset peripheral to a clean slate state
-                rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                $TIMX::enable(rcc);
+                $TIMX::reset(rcc);
 
                 if PINS::C1 {
                     tim.ccmr1_output().modify(|_, w| w.oc1pe().set_bit().oc1m().bits(6));
@@ -606,17 +764,7 @@ macro_rules! pwm_2_channels {
                     tim.ccmr1_output().modify(|_, w| w.oc2pe().set_bit().oc2m().bits(6));
                 }
 
-                // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                let tclk = if rcc.clocks.hclk().0 == rcc.clocks.pclk().0 {
-                    rcc.clocks.pclk().0
-                } else {
-                    rcc.clocks.pclk().0 * 2
-                };
-                let ticks = tclk / freq.into().0;
-
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
                 tim.psc.write(|w| w.psc().bits(psc) );
-                let arr = u16(ticks / u32(psc + 1)).unwrap();
                 tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
 
                 // enable auto-reload preload
@@ -635,7 +783,7 @@ macro_rules! pwm_2_channels {
                         .set_bit()
                 );
                 //NOTE(unsafe) `PINS::Channels` is a ZST
-                unsafe { MaybeUninit::uninit().assume_init() }
+                Ok(unsafe { MaybeUninit::uninit().assume_init() })
             }
 
             impl hal::PwmPin for PwmChannels<$TIMX, C1> {
@@ -702,50 +850,66 @@ macro_rules! pwm_2_channels {
 // General purpose timer with one output channel (TIM14)
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
 macro_rules! pwm_1_channel {
-    ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIMX:ident: $timX:ident,)+) => {
         $(
-            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> PINS::Channels
+            pub fn $timX<P, PINS, T>(tim: $TIMX, pins: PINS, rcc: &mut Rcc, freq: T) -> Result<PINS::Channels, Error>
             where
                 PINS: Pins<$TIMX, P>,
                 T: Into<Hertz>,
             {
-                // enable and reset peripheral to a clean slate state
-                rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                Pwm::$timX(tim, pins, rcc, freq).map(|(_, channels)| channels)
+            }
 
-                if PINS::C1 {
-                    tim.ccmr1_output().modify(|_, w| unsafe { w.oc1pe().set_bit().oc1m().bits(6) });
+            impl Pwm<$TIMX> {
+                /// Configures a TIM peripheral for PWM output, returning the timer handle and the channels
+                pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> Result<(Self, PINS::Channels), Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk();
+                    let (psc, arr) = compute_psc_arr(tclk, freq.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIMX::enable(rcc);
+                    $TIMX::reset(rcc);
+
+                    if PINS::C1 {
+                        tim.ccmr1_output().modify(|_, w| unsafe { w.oc1pe().set_bit().oc1m().bits(6) });
+                    }
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+
+                    // enable auto-reload preload
+                    tim.cr1.modify(|_, w| w.arpe().set_bit());
+
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    brk!($TIMX, tim);
+                    tim.cr1.write(|w|
+                        w.cen()
+                            .set_bit()
+                    );
+                    let pwm = Pwm {
+                        tim,
+                        clk: tclk,
+                    };
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok((pwm, unsafe { MaybeUninit::uninit().assume_init() }))
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIMX {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIMX::disable_unchecked() };
+                    self.tim
                 }
-
-                // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                let tclk = if rcc.clocks.hclk().0 == rcc.clocks.pclk().0 {
-                    rcc.clocks.pclk().0
-                } else {
-                    rcc.clocks.pclk().0 * 2
-                };
-                let ticks = tclk / freq.into().0;
-
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                tim.psc.write(|w| unsafe { w.psc().bits(psc) });
-                let arr = u16(ticks / u32(psc + 1)).unwrap();
-                tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
-
-                // enable auto-reload preload
-                tim.cr1.modify(|_, w| w.arpe().set_bit());
-
-                // Trigger update event to load the registers
-                tim.cr1.modify(|_, w| w.urs().set_bit());
-                tim.egr.write(|w| w.ug().set_bit());
-                tim.cr1.modify(|_, w| w.urs().clear_bit());
-
-                brk!($TIMX, tim);
-                tim.cr1.write(|w|
-                    w.cen()
-                        .set_bit()
-                );
-                //NOTE(unsafe) `PINS::Channels` is a ZST
-                unsafe { MaybeUninit::uninit().assume_init() }
             }
 
             impl hal::PwmPin for PwmChannels<$TIMX, C1> {
@@ -783,53 +947,69 @@ macro_rules! pwm_1_channel {
 // General purpose timer with one output channel (TIM16/TIM17)
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
 macro_rules! pwm_1_channel_with_complementary_outputs {
-    ($($TIMX:ident: ($timX:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIMX:ident: $timX:ident,)+) => {
         $(
-            pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> PINS::Channels
+            pub fn $timX<P, PINS, T>(tim: $TIMX, pins: PINS, rcc: &mut Rcc, freq: T) -> Result<PINS::Channels, Error>
             where
                 PINS: Pins<$TIMX, P>,
                 T: Into<Hertz>,
             {
-                // enable and reset peripheral to a clean slate state
-                rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                Pwm::$timX(tim, pins, rcc, freq).map(|(_, channels)| channels)
+            }
 
-                if PINS::C1 {
-                    tim.ccmr1_output().modify(|_, w| w.oc1pe().set_bit().oc1m().bits(6));
+            impl Pwm<$TIMX> {
+                /// Configures a TIM peripheral for PWM output, returning the timer handle and the channels
+                pub fn $timX<P, PINS, T>(tim: $TIMX, _pins: PINS, rcc: &mut Rcc, freq: T) -> Result<(Self, PINS::Channels), Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    let tclk = rcc.clocks.timer_clk();
+                    let (psc, arr) = compute_psc_arr(tclk, freq.into())?;
+
+                    // enable and reset peripheral to a clean slate state
+                    $TIMX::enable(rcc);
+                    $TIMX::reset(rcc);
+
+                    if PINS::C1 {
+                        tim.ccmr1_output().modify(|_, w| w.oc1pe().set_bit().oc1m().bits(6));
+                    }
+
+                    tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+
+                    // enable auto-reload preload
+                    tim.cr1.modify(|_, w| w.arpe().set_bit());
+
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    brk!($TIMX, tim);
+                    tim.cr1.write(|w|
+                        w.opm()
+                            .clear_bit()
+                            .cen()
+                            .set_bit()
+                    );
+
+                    let pwm = Pwm {
+                        tim,
+                        clk: tclk,
+                    };
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok((pwm, unsafe { MaybeUninit::uninit().assume_init() }))
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIMX {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIMX::disable_unchecked() };
+                    self.tim
                 }
-
-                // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                let tclk = if rcc.clocks.hclk().0 == rcc.clocks.pclk().0 {
-                    rcc.clocks.pclk().0
-                } else {
-                    rcc.clocks.pclk().0 * 2
-                };
-                let ticks = tclk / freq.into().0;
-
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                tim.psc.write(|w| unsafe { w.psc().bits(psc) });
-                let arr = u16(ticks / u32(psc + 1)).unwrap();
-                tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
-
-                // enable auto-reload preload
-                tim.cr1.modify(|_, w| w.arpe().set_bit());
-
-                // Trigger update event to load the registers
-                tim.cr1.modify(|_, w| w.urs().set_bit());
-                tim.egr.write(|w| w.ug().set_bit());
-                tim.cr1.modify(|_, w| w.urs().clear_bit());
-
-                brk!($TIMX, tim);
-                tim.cr1.write(|w|
-                    w.opm()
-                        .clear_bit()
-                        .cen()
-                        .set_bit()
-                );
-
-                //NOTE(unsafe) `PINS::Channels` is a ZST
-                unsafe { MaybeUninit::uninit().assume_init() }
             }
 
             impl hal::PwmPin for PwmChannels<$TIMX, C1> {
@@ -864,22 +1044,376 @@ macro_rules! pwm_1_channel_with_complementary_outputs {
     };
 }
 
+// the following timers can count in center-aligned mode, which halves the PWM frequency
+macro_rules! center_aligned {
+    (TIM1, $tim:expr) => {
+        $tim.cr1.read().cms().bits() != 0
+    };
+    (TIM3, $tim:expr) => {
+        $tim.cr1.read().cms().bits() != 0
+    };
+    ($_other:ident, $_tim:expr) => {
+        false
+    };
+}
+
+// Settings shared by all PWM timers
+macro_rules! pwm_timer {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Pwm<$TIMX> {
+                /// Changes the PWM frequency
+                ///
+                /// The new period takes effect at the next update event. Duty cycles are
+                /// not scaled, they have to be set again relative to the new `get_max_duty`.
+                pub fn set_frequency<T>(&mut self, freq: T) -> Result<(), Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let (psc, arr) = if center_aligned!($TIMX, self.tim) {
+                        compute_psc_arr_center(self.clk, freq.into())?
+                    } else {
+                        compute_psc_arr(self.clk, freq.into())?
+                    };
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+                    Ok(())
+                }
+
+                /// Returns the PWM frequency actually reached
+                pub fn get_frequency(&self) -> Hertz {
+                    pwm_frequency(
+                        self.clk,
+                        self.tim.psc.read().psc().bits(),
+                        self.tim.arr.read().bits() as u16,
+                        center_aligned!($TIMX, self.tim),
+                    )
+                }
+
+                /// Sets the raw period (auto-reload value) in timer ticks
+                ///
+                /// The new period takes effect at the next update event.
+                pub fn set_period(&mut self, period: u16) {
+                    self.tim.arr.write(|w| unsafe { w.bits(u32(period)) });
+                }
+
+                /// Returns the raw period (auto-reload value) in timer ticks
+                pub fn get_period(&self) -> u16 {
+                    self.tim.arr.read().bits() as u16
+                }
+
+                /// Returns the maximum duty cycle of all channels, equal to the period
+                pub fn get_max_duty(&self) -> u16 {
+                    self.get_period()
+                }
+            }
+        )+
+    };
+}
+
+// the following timers can count down and in center-aligned mode
+macro_rules! pwm_count_mode {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Pwm<$TIMX> {
+                /// Changes the counting mode
+                ///
+                /// The counter is stopped while switching. Center-aligned modes count up
+                /// and down, halving the PWM frequency until `set_frequency` is called again.
+                pub fn set_count_mode(&mut self, mode: CountMode) {
+                    let (cms, dir) = match mode {
+                        CountMode::Up => (0b00, false),
+                        CountMode::Down => (0b00, true),
+                        CountMode::CenterAligned1 => (0b01, false),
+                        CountMode::CenterAligned2 => (0b10, false),
+                        CountMode::CenterAligned3 => (0b11, false),
+                    };
+
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.cr1.modify(|_, w| unsafe { w.cms().bits(cms).dir().bit(dir) });
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Returns the counting mode
+                pub fn get_count_mode(&self) -> CountMode {
+                    let cr1 = self.tim.cr1.read();
+                    match (cr1.cms().bits(), cr1.dir().bit_is_set()) {
+                        (0b00, false) => CountMode::Up,
+                        (0b00, true) => CountMode::Down,
+                        (0b01, _) => CountMode::CenterAligned1,
+                        (0b10, _) => CountMode::CenterAligned2,
+                        _ => CountMode::CenterAligned3,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+// Timer with break input, dead time generator and main output switch
+macro_rules! pwm_advanced {
+    ($($TIMX:ident: [$($CH:ident: ($ccXp:ident, $oisX:ident),)+],)+) => {
+        $(
+            impl Pwm<$TIMX> {
+                /// Sets the dead time inserted between complementary outputs in nanoseconds
+                ///
+                /// The dead time is rounded down to the resolution of the `DTG` encoding.
+                /// Dead times beyond 1023 timer clock cycles increase the clock division
+                /// (`CKD`), which also slows down the sampling clock of the input filters.
+                pub fn set_dead_time(&mut self, ns: u32) {
+                    let ticks = u64::from(self.clk.0) * u64::from(ns) / 1_000_000_000;
+                    let ckd: u8 = match ticks {
+                        0..=1023 => 0,
+                        1024..=2047 => 1,
+                        _ => 2,
+                    };
+                    let dtg = dead_time_generator((ticks >> ckd).min(1023) as u16);
+                    self.tim.cr1.modify(|_, w| unsafe { w.ckd().bits(ckd) });
+                    self.tim.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg) });
+                }
+
+                /// Returns the dead time in nanoseconds
+                pub fn get_dead_time(&self) -> u32 {
+                    let ckd = self.tim.cr1.read().ckd().bits();
+                    let ticks = dead_time_ticks(self.tim.bdtr.read().dtg().bits()) << ckd;
+                    (u64::from(ticks) * 1_000_000_000 / u64::from(self.clk.0)) as u32
+                }
+
+                /// Enables the break function on the `BKIN` pin
+                ///
+                /// The BDTR register has no break filter (`BKF`) field, the pin is sampled
+                /// unfiltered and glitches have to be suppressed externally.
+                pub fn enable_break<PIN>(&mut self, _pin: &PIN, polarity: BreakPolarity)
+                where
+                    PIN: BkinPin<$TIMX>,
+                {
+                    self.enable_internal_break(polarity);
+                }
+
+                /// Enables the break function for internal sources only
+                ///
+                /// This is used when the break is driven by a comparator, see `comp::OutputRoute`.
+                pub fn enable_internal_break(&mut self, polarity: BreakPolarity) {
+                    self.tim.bdtr.modify(|_, w| {
+                        w.bkp()
+                            .bit(polarity == BreakPolarity::ActiveHigh)
+                            .bke()
+                            .set_bit()
+                    });
+                }
+
+                /// Disables the break function
+                pub fn disable_break(&mut self) {
+                    self.tim.bdtr.modify(|_, w| w.bke().clear_bit());
+                }
+
+                /// Returns `true` if a break event occurred
+                pub fn is_break_pending(&self) -> bool {
+                    self.tim.sr.read().bif().bit_is_set()
+                }
+
+                /// Clears the break flag
+                pub fn clear_break(&mut self) {
+                    self.tim.sr.modify(|_, w| w.bif().clear_bit());
+                }
+
+                /// Write protects parts of the configuration until the next reset
+                pub fn set_lock_level(&mut self, level: LockLevel) {
+                    self.tim.bdtr.modify(|_, w| unsafe { w.lock().bits(level as u8) });
+                }
+
+                /// Selects whether disabled channels drive their inactive level (`true`) or
+                /// float while the main output is enabled (OSSR)
+                pub fn set_off_state_run(&mut self, drive: bool) {
+                    self.tim.bdtr.modify(|_, w| w.ossr().bit(drive));
+                }
+
+                /// Selects whether the channels drive their idle level (`true`) or float
+                /// while the main output is disabled (OSSI)
+                pub fn set_off_state_idle(&mut self, drive: bool) {
+                    self.tim.bdtr.modify(|_, w| w.ossi().bit(drive));
+                }
+
+                /// Sets the active level of an output
+                pub fn set_polarity(&mut self, channel: Channel, polarity: Polarity) {
+                    let low = polarity == Polarity::ActiveLow;
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$CH => self.tim.ccer.modify(|_, w| w.$ccXp().bit(low)),)+
+                        _ => {}
+                    }
+                }
+
+                /// Sets the level of an output while the main output is disabled
+                pub fn set_idle_state(&mut self, channel: Channel, state: IdleState) {
+                    let set = state == IdleState::Set;
+                    #[allow(unreachable_patterns)]
+                    match channel {
+                        $(Channel::$CH => self.tim.cr2.modify(|_, w| w.$oisX().bit(set)),)+
+                        _ => {}
+                    }
+                }
+
+                /// Enables the outputs
+                pub fn enable_main_output(&mut self) {
+                    self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+                }
+
+                /// Disables the outputs, they go to their idle state
+                pub fn disable_main_output(&mut self) {
+                    self.tim.bdtr.modify(|_, w| w.moe().clear_bit());
+                }
+
+                /// Returns `true` if the outputs are enabled
+                ///
+                /// A break event disables the outputs.
+                pub fn is_main_output_enabled(&self) -> bool {
+                    self.tim.bdtr.read().moe().bit_is_set()
+                }
+
+                /// Selects whether the outputs are enabled again at the next update
+                /// event after a break (AOE)
+                pub fn set_automatic_output(&mut self, enable: bool) {
+                    self.tim.bdtr.modify(|_, w| w.aoe().bit(enable));
+                }
+
+                /// Sets the number of counter periods between two update events
+                ///
+                /// With `repetitions = n` the shadow registers are updated every `n + 1`
+                /// periods, in center-aligned mode every `n + 1` half periods.
+                pub fn set_repetition_counter(&mut self, repetitions: u8) {
+                    self.tim.rcr.write(|w| unsafe { w.rep().bits(repetitions) });
+                }
+            }
+        )+
+    };
+}
+
 use crate::pac::*;
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
-pwm_4_channels!(TIM3: (tim3, tim3en, tim3rst, apbenr1, apbrstr1),);
+pwm_4_channels!(TIM3: tim3,);
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pwm_timer!(TIM3,);
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pwm_count_mode!(TIM3,);
+
+pwm_4_channels_with_3_complementary_outputs!(TIM1: tim1,);
+
+pwm_timer!(TIM1,);
+pwm_count_mode!(TIM1,);
+
+pwm_advanced!(
+    TIM1: [
+        C1: (cc1p, ois1),
+        C1N: (cc1np, ois1n),
+        C2: (cc2p, ois2),
+        C2N: (cc2np, ois2n),
+        C3: (cc3p, ois3),
+        C3N: (cc3np, ois3n),
+        C4: (cc4p, ois4),
+    ],
+);
 
-pwm_4_channels_with_3_complementary_outputs!(TIM1: (tim1, tim1en, tim1rst, apbenr2, apbrstr2),);
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+pwm_1_channel!(TIM14: tim14,);
 
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
-pwm_1_channel!(TIM14: (tim14, tim14en, tim14rst, apbenr2, apbrstr2),);
+pwm_timer!(TIM14,);
 
 // TIM16 is available for all devices but it can not be used for PWM for py32f002a
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
-pwm_1_channel_with_complementary_outputs!(
-    TIM16: (tim16, tim16en, tim16rst, apbenr2, apbrstr2),
-);
+pwm_1_channel_with_complementary_outputs!(TIM16: tim16,);
 
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
-pwm_1_channel_with_complementary_outputs!(
-    TIM17: (tim17, tim17en, tim17rst, apbenr2, apbrstr2),
+pwm_1_channel_with_complementary_outputs!(TIM17: tim17,);
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pwm_timer!(TIM16, TIM17,);
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pwm_advanced!(
+    TIM16: [
+        C1: (cc1p, ois1),
+        C1N: (cc1np, ois1n),
+    ],
+    TIM17: [
+        C1: (cc1p, ois1),
+        C1N: (cc1np, ois1n),
+    ],
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_time_encoding() {
+        assert_eq!(dead_time_generator(0), 0x00);
+        assert_eq!(dead_time_generator(127), 0x7F);
+        assert_eq!(dead_time_generator(128), 0x80);
+        assert_eq!(dead_time_generator(255), 0xBF);
+        assert_eq!(dead_time_generator(256), 0xC0);
+        assert_eq!(dead_time_generator(511), 0xDF);
+        assert_eq!(dead_time_generator(512), 0xE0);
+        assert_eq!(dead_time_generator(1008), 0xFF);
+        // Saturates at the longest dead time
+        assert_eq!(dead_time_generator(u16::MAX), 0xFF);
+    }
+
+    #[test]
+    fn dead_time_rounds_down() {
+        for ticks in 0..=1008_u16 {
+            let step = match ticks {
+                0..=127 => 1,
+                128..=255 => 2,
+                256..=511 => 8,
+                _ => 16,
+            };
+            let reached = dead_time_ticks(dead_time_generator(ticks));
+            assert!(reached <= u32::from(ticks), "{} ticks", ticks);
+            assert!(u32::from(ticks) - reached < step, "{} ticks", ticks);
+        }
+    }
+
+    #[test]
+    fn center_aligned_period() {
+        let tclk = Hertz(48_000_000);
+        for &freq in &[1, 10, 366, 1_000, 20_000, 12_000_000] {
+            let (psc, arr) = compute_psc_arr_center(tclk, Hertz(freq)).unwrap();
+            let reached = pwm_frequency(tclk, psc, arr, true).0;
+            assert!(reached >= freq, "{} Hz", freq);
+            assert!(reached - freq <= freq / 100 + 1, "{} Hz", freq);
+        }
+        assert_eq!(compute_psc_arr_center(tclk, Hertz(1_000)), Ok((0, 24_000)));
+        assert_eq!(
+            compute_psc_arr_center(tclk, Hertz(24_000_001)),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            compute_psc_arr_center(tclk, Hertz(0)),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn frequency_from_psc_arr() {
+        let tclk = Hertz(48_000_000);
+        assert_eq!(pwm_frequency(tclk, 0, 47_999, false).0, 1_000);
+        assert_eq!(pwm_frequency(tclk, 47, 999, false).0, 1_000);
+        assert_eq!(pwm_frequency(tclk, 0, 24_000, true).0, 1_000);
+        assert_eq!(pwm_frequency(tclk, 0, 0, false).0, 48_000_000);
+        // A center-aligned counter with ARR = 0 does not run
+        assert_eq!(pwm_frequency(tclk, 0, 0, true).0, 0);
+        assert_eq!(pwm_frequency(tclk, 0xFFFF, 0, true).0, 0);
+    }
+
+    #[test]
+    fn dead_time_decoding() {
+        for dtg in 0..=0xFF_u8 {
+            let ticks = dead_time_ticks(dtg);
+            assert_eq!(dead_time_ticks(dead_time_generator(ticks as u16)), ticks);
+        }
+    }
+}
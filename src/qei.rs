@@ -0,0 +1,211 @@
+//! API for the quadrature encoder interface of TIM1 and TIM3
+//!
+//! The encoder signals A and B are connected to channel 1 and 2 of the timer,
+//! which counts the edges up or down depending on the direction of rotation.
+//!
+//! # Example
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::qei::{Config, Qei};
+//!
+//! cortex_m::interrupt::free(|cs| {
+//!     let mut p = pac::Peripherals::take().unwrap();
+//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
+//!
+//!     let gpioa = p.GPIOA.split(&mut rcc);
+//!     let pins = (gpioa.pa6.into_alternate_af1(cs), gpioa.pa7.into_alternate_af1(cs));
+//!
+//!     let mut qei = Qei::tim3(p.TIM3, pins, Config::default().filter(4), &mut rcc);
+//!
+//!     loop {
+//!         // Poll at least every 32768 counts to keep track of the position
+//!         let position = qei.position();
+//!     }
+//! });
+//! ```
+
+use crate::rcc::{Enable, Rcc, Reset};
+use crate::timers::{PinC1, PinC2};
+
+use embedded_hal as hal;
+
+pub use embedded_hal::Direction;
+
+/// Quadrature encoder interface
+pub struct Qei<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    last: u16,
+    position: i32,
+}
+
+/// Edges counted by the encoder interface
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Encoder mode 1, count on the edges of TI2 only
+    Ti2 = 0b001,
+    /// Encoder mode 2, count on the edges of TI1 only
+    Ti1 = 0b010,
+    /// Encoder mode 3, count on the edges of both TI1 and TI2
+    Ti1AndTi2 = 0b011,
+}
+
+/// Encoder interface configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    mode: Mode,
+    filter: u8,
+    invert: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Ti1AndTi2,
+            filter: 0,
+            invert: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Digital filter applied to both inputs, `0` disables it (see the `ICxF` field of the reference manual)
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter & 0xF;
+        self
+    }
+
+    /// Inverts TI1 and with it the counting direction
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+}
+
+macro_rules! qei {
+    ($($TIM:ident: $tim:ident,)+) => {
+        $(
+            use crate::pac::$TIM;
+
+            impl<PC1, PC2> Qei<$TIM, (PC1, PC2)>
+            where
+                PC1: PinC1<$TIM>,
+                PC2: PinC2<$TIM>,
+            {
+                /// Configures a TIM peripheral as quadrature encoder interface
+                pub fn $tim(tim: $TIM, pins: (PC1, PC2), config: Config, rcc: &mut Rcc) -> Self {
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    // Map TI1FP1 to channel 1 and TI2FP2 to channel 2
+                    tim.ccmr1_input().write(|w| unsafe {
+                        w.cc1s()
+                            .bits(0b01)
+                            .ic1f()
+                            .bits(config.filter)
+                            .cc2s()
+                            .bits(0b01)
+                            .ic2f()
+                            .bits(config.filter)
+                    });
+                    tim.ccer.write(|w| {
+                        w.cc1p()
+                            .bit(config.invert)
+                            .cc1e()
+                            .set_bit()
+                            .cc2p()
+                            .clear_bit()
+                            .cc2e()
+                            .set_bit()
+                    });
+
+                    tim.smcr.modify(|_, w| unsafe { w.sms().bits(config.mode as u8) });
+                    tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Qei {
+                        tim,
+                        pins,
+                        last: 0,
+                        position: 0,
+                    }
+                }
+            }
+
+            impl<PINS> Qei<$TIM, PINS> {
+                /// Returns the raw 16 bit counter value
+                pub fn count(&self) -> u16 {
+                    self.tim.cnt.read().bits() as u16
+                }
+
+                /// Returns the direction of the last counted edge
+                pub fn direction(&self) -> Direction {
+                    if self.tim.cr1.read().dir().bit_is_set() {
+                        Direction::Downcounting
+                    } else {
+                        Direction::Upcounting
+                    }
+                }
+
+                /// Returns the position extended to 32 bits
+                ///
+                /// Overflows of the counter are tracked from the difference to the last
+                /// call, so this has to be called at least every 32768 counts.
+                pub fn position(&mut self) -> i32 {
+                    let count = self.count();
+                    self.position = self
+                        .position
+                        .wrapping_add(i32::from(count.wrapping_sub(self.last) as i16));
+                    self.last = count;
+                    self.position
+                }
+
+                /// Resets the counter and the position to zero
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                    self.last = 0;
+                    self.position = 0;
+                }
+
+                /// Disables the timer and releases the TIM peripheral and the pins
+                pub fn release(self) -> ($TIM, PINS) {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    (self.tim, self.pins)
+                }
+            }
+
+            impl<PINS> hal::Qei for Qei<$TIM, PINS> {
+                type Count = u16;
+
+                fn count(&self) -> u16 {
+                    Qei::count(self)
+                }
+
+                fn direction(&self) -> Direction {
+                    Qei::direction(self)
+                }
+            }
+        )+
+    };
+}
+
+qei! {
+    TIM1: tim1,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+qei! {
+    TIM3: tim3,
+}
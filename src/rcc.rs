@@ -4,9 +4,15 @@ use crate::pac::rcc::{
     icscr::HSI_FS_A,
 };
 
+use crate::gpio::{gpioa, Alternate, AF15};
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+use crate::gpio::{gpiof, AF5};
 use crate::pac::RCC;
 use crate::time::Hertz;
 
+mod enable;
+pub use self::enable::*;
+
 /// Extension trait that sets up the `RCC` peripheral
 pub trait RccExt {
     /// Configure the clocks of the RCC peripheral
@@ -16,10 +22,7 @@ pub trait RccExt {
 impl RccExt for RCC {
     fn configure(self) -> CFGR {
         CFGR {
-            hclk: None,
-            pclk: None,
-            sysclk: None,
-            clock_src: SysClkSource::HSISYS(HSIFreq::Freq24mhz),
+            config: Config::default(),
             rcc: self,
         }
     }
@@ -37,6 +40,288 @@ impl Rcc {
             .cfgr
             .modify(|_, w| w.mcopre().variant(pre.into()).mcosel().variant(sel.into()));
     }
+
+    /// Switches to a new clock configuration at runtime
+    ///
+    /// The flash wait states are adjusted on the safe side of the switch and the PLL
+    /// and HSE are stopped when no longer needed. Drivers which were set up with the
+    /// old `clocks` have to be updated through [`ClockDependent::update_clocks`].
+    /// On error the clocks keep running unchanged. If the FLASH peripheral is owned
+    /// by a [`Flash`](crate::flash::Flash) pass in its [`regs_mut`](crate::flash::Flash::regs_mut).
+    pub fn reconfigure(
+        &mut self,
+        config: Config,
+        flash: &mut crate::pac::FLASH,
+    ) -> Result<(), Error> {
+        self.clocks = config.apply(&mut self.regs, flash)?;
+        Ok(())
+    }
+
+    /// Returns the current HSI trim value
+    pub fn hsi_trim(&self) -> u16 {
+        self.regs.icscr.read().hsi_trim().bits()
+    }
+
+    /// Adjusts the HSI trim value by `delta` steps and returns the new value
+    ///
+    /// Higher values increase the frequency. `HSIFreq` selection loads the factory
+    /// trim again, and `clocks` keep reporting the nominal frequency.
+    pub fn trim_hsi(&mut self, delta: i16) -> u16 {
+        let trim = (i32::from(self.hsi_trim()) + i32::from(delta))
+            .clamp(0, i32::from(self::inner::HSI_TRIM_MAX)) as u16;
+        self.regs
+            .icscr
+            .modify(|_, w| unsafe { w.hsi_trim().bits(trim) });
+        trim
+    }
+
+    /// Returns the cause of the last reset
+    ///
+    /// The flags are sticky, they accumulate over multiple resets until cleared
+    /// with `clear_reset_reason`. If several are set the most specific one is reported.
+    pub fn reset_reason(&self) -> ResetReason {
+        let csr = self.regs.csr.read();
+        if csr.pwrrstf().bit_is_set() {
+            ResetReason::PowerOn
+        } else if csr.lpwrrstf().bit_is_set() {
+            ResetReason::LowPower
+        } else if csr.wwdgrstf().bit_is_set() {
+            ResetReason::WindowWatchdog
+        } else if csr.iwdgrstf().bit_is_set() {
+            ResetReason::IndependentWatchdog
+        } else if csr.sftrstf().bit_is_set() {
+            ResetReason::Software
+        } else if csr.oblrstf().bit_is_set() {
+            ResetReason::OptionByteLoad
+        } else if csr.pinrstf().bit_is_set() {
+            // Internal resets also drive NRST low, so check this last
+            ResetReason::Pin
+        } else {
+            ResetReason::Unknown
+        }
+    }
+
+    /// Clears the reset flags
+    pub fn clear_reset_reason(&mut self) {
+        self.regs.csr.modify(|_, w| w.rmvf().set_bit());
+    }
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+impl Rcc {
+    /// Trims the HSI against the 32.768 kHz LSE
+    ///
+    /// The LSE is started and routed through MCO to TIM14, MCO is restored afterwards.
+    /// See [`Rcc::auto_trim_hsi`].
+    #[cfg(feature = "py32f030")]
+    pub fn auto_trim_hsi_lse(&mut self, tim: &mut crate::pac::TIM14, mode: LSEMode) -> u16 {
+        self::inner::lse_enable(&mut self.regs, &mode);
+
+        let cfgr = self.regs.cfgr.read();
+        let (mcosel, mcopre) = (cfgr.mcosel().bits(), cfgr.mcopre().bits());
+        self.configure_mco(MCOSrc::Lse, MCODiv::NotDivided);
+
+        // TI1 connected to MCO
+        let trim = self.auto_trim(tim, 0b11, Hertz(self::inner::LSE_FREQ));
+
+        self.regs
+            .cfgr
+            .modify(|_, w| unsafe { w.mcosel().bits(mcosel).mcopre().bits(mcopre) });
+        trim
+    }
+
+    /// Trims the HSI against a reference clock of `freq` on the channel 1 input of TIM14
+    ///
+    /// The system clock has to be derived from the HSI. The trim value is stepped until
+    /// the measured period of the reference matches best, the chosen value is returned.
+    /// The TIM14 peripheral is reset afterwards.
+    pub fn auto_trim_hsi<PIN>(
+        &mut self,
+        tim: &mut crate::pac::TIM14,
+        _pin: &PIN,
+        freq: Hertz,
+    ) -> u16
+    where
+        PIN: crate::timers::PinC1<crate::pac::TIM14>,
+    {
+        // TI1 connected to the GPIO
+        self.auto_trim(tim, 0b00, freq)
+    }
+
+    fn auto_trim(&mut self, tim: &mut crate::pac::TIM14, remap: u32, freq: Hertz) -> u16 {
+        // Number of reference cycles between two captures
+        const IC_PRESCALER: u64 = 8;
+        const MEASUREMENTS: u64 = 8;
+        const MAX_STEPS: u32 = 64;
+
+        crate::pac::TIM14::enable(self);
+        crate::pac::TIM14::reset(self);
+
+        let tclk = self.clocks.timer_clk().0;
+
+        // Scale the timer so one capture period fits into the counter
+        let ticks = u64::from(tclk) * IC_PRESCALER / u64::from(freq.0);
+        let psc = ticks / (1 << 16);
+        let expected = ticks * MEASUREMENTS / (psc + 1);
+
+        // Capture every 8th rising edge
+        tim.or.write(|w| unsafe { w.bits(remap) });
+        tim.ccmr1_input()
+            .write(|w| unsafe { w.cc1s().bits(0b01).ic1psc().bits(0b11) });
+        tim.ccer.write(|w| w.cc1e().set_bit());
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        let measure = |tim: &mut crate::pac::TIM14| {
+            let mut capture = || {
+                tim.sr.write(|w| unsafe { w.bits(0) });
+                while tim.sr.read().cc1if().bit_is_clear() {}
+                tim.ccr1.read().bits() as u16
+            };
+
+            let mut last = capture();
+            let mut ticks = 0;
+            for _ in 0..MEASUREMENTS {
+                let now = capture();
+                ticks += u64::from(now.wrapping_sub(last));
+                last = now;
+            }
+            ticks
+        };
+
+        let mut trim = self.hsi_trim();
+        let mut best = (trim, u64::MAX);
+        let mut last_faster = None;
+        for _ in 0..MAX_STEPS {
+            let ticks = measure(tim);
+            let error = if ticks > expected {
+                ticks - expected
+            } else {
+                expected - ticks
+            };
+            if error < best.1 {
+                best = (trim, error);
+            }
+
+            // A fast HSI counts more ticks per reference period, stop once the target was crossed
+            let faster = ticks > expected;
+            if error == 0 || last_faster.map_or(false, |last| last != faster) {
+                break;
+            }
+            last_faster = Some(faster);
+
+            let next = self.trim_hsi(if faster { -1 } else { 1 });
+            if next == trim {
+                break;
+            }
+            trim = next;
+        }
+
+        self.regs
+            .icscr
+            .modify(|_, w| unsafe { w.hsi_trim().bits(best.0) });
+
+        crate::pac::TIM14::reset(self);
+        best.0
+    }
+}
+
+/// Cause of the last reset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power-on or brown-out reset
+    PowerOn,
+    /// Reset pin (NRST) was pulled low
+    Pin,
+    /// Software reset through `SCB::sys_reset`
+    Software,
+    /// Independent watchdog timed out
+    IndependentWatchdog,
+    /// Window watchdog timed out or was refreshed outside of the window
+    WindowWatchdog,
+    /// Option bytes were reloaded
+    OptionByteLoad,
+    /// Stop mode was entered with low-power reset enabled in the option bytes
+    LowPower,
+    /// No reset flag is set, e.g. because they were cleared since
+    Unknown,
+}
+
+/// Pins which can output the MCO signal
+pub trait McoPin {}
+
+macro_rules! mco_pins {
+    ($($PIN:ty,)+) => {
+        $(
+            impl McoPin for $PIN {}
+        )+
+    };
+}
+
+mco_pins! {
+    gpioa::PA1<Alternate<AF15>>,
+    gpioa::PA5<Alternate<AF15>>,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+mco_pins! {
+    gpioa::PA13<Alternate<AF5>>,
+    gpiof::PF2<Alternate<AF15>>,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f002a"))]
+mco_pins! {
+    gpioa::PA8<Alternate<AF5>>,
+    gpioa::PA9<Alternate<AF5>>,
+}
+
+/// Clock output on a MCO pin
+pub struct Mco<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> Mco<PIN>
+where
+    PIN: McoPin,
+{
+    /// Outputs `sel` divided by `pre` on `pin`
+    ///
+    /// LSI, HSI and LSE (as crystal with medium drive) are started if they are not
+    /// running yet. HSE and PLL are only available while used by the system clock.
+    pub fn new(pin: PIN, sel: MCOSrc, pre: MCODiv, rcc: &mut Rcc) -> Self {
+        let mut mco = Mco { pin };
+        mco.set_source(sel, pre, rcc);
+        mco
+    }
+
+    /// Changes the clock source and prescaler
+    pub fn set_source(&mut self, sel: MCOSrc, pre: MCODiv, rcc: &mut Rcc) {
+        match sel {
+            MCOSrc::Lsi => self::inner::lsi_enable(&mut rcc.regs),
+            MCOSrc::Hsi => {
+                rcc.regs.cr.modify(|_, w| w.hsion().set_bit());
+                while rcc.regs.cr.read().hsirdy().bit_is_clear() {}
+            }
+            #[cfg(feature = "py32f030")]
+            MCOSrc::Lse => {
+                if rcc.regs.bdcr.read().lserdy().bit_is_clear() {
+                    self::inner::lse_enable(&mut rcc.regs, &LSEMode::Crystal(LSEDrive::Medium));
+                }
+            }
+            _ => {}
+        }
+        rcc.configure_mco(sel, pre);
+    }
+
+    /// Stops the clock output and releases the pin
+    pub fn release(self) -> PIN {
+        let rcc = unsafe { &(*crate::pac::RCC::ptr()) };
+        rcc.cfgr
+            .modify(|_, w| w.mcosel().variant(MCOSrc::NoClock.into()));
+        self.pin
+    }
 }
 
 /// MCO source select
@@ -46,7 +331,7 @@ pub enum MCOSrc {
     NoClock = 0,
     ///1: SYSCLK clock selected
     Sysclk = 1,
-    ///3: MSI oscillator clock selected
+    ///3: HSI oscillator clock selected
     Hsi = 3,
     ///4: HSE oscillator clock selected
     Hse = 4,
@@ -161,60 +446,142 @@ impl From<HSIFreq> for HSI_FS_A {
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum HSEBypassMode {
     /// Not bypassed: for crystals
     NotBypassed,
     /// Bypassed: for external clock sources
     Bypassed,
 }
+
+/// LSE crystal driver strength
+#[cfg(feature = "py32f030")]
+#[derive(Clone, Copy)]
+pub enum LSEDrive {
+    Low = 0b01,
+    Medium = 0b10,
+    High = 0b11,
+}
+
+/// LSE configuration
+#[cfg(feature = "py32f030")]
+#[derive(Clone, Copy)]
+pub enum LSEMode {
+    /// Crystal driven with the given strength
+    Crystal(LSEDrive),
+    /// Bypassed: for external clock sources
+    Bypassed,
+}
+
+/// PLL input clock
+#[cfg(feature = "py32f030")]
+#[derive(Clone, Copy)]
+pub enum PLLSrc {
+    /// HSI oscillator
+    HSI(HSIFreq),
+    /// High-speed external clock(freq,bypassed)
+    HSE(Hertz, HSEBypassMode),
+}
+
+/// Clock configuration error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The requested sysclk can not be derived from the selected clock source
+    SysclkUnreachable,
+    /// The requested hclk is higher than sysclk
+    HclkUnreachable,
+    /// The requested pclk is higher than hclk
+    PclkUnreachable,
+    /// The PLL input clock is outside of the 16 MHz to 24 MHz range
+    PllInputOutOfRange,
+    /// The requested sysclk needs the PLL, which is only available on py32f030
+    PllUnavailable,
+}
+
 /// RCC for F0x0 devices
 //#[cfg(any(feature = "py32f030", feature = "py32f003"))]
 mod inner {
+    use core::ptr;
+
     use crate::pac::{rcc::cfgr::SW_A, RCC};
 
-    use super::{HSEBypassMode, HSIFreq};
+    use super::{Error, HSEBypassMode, HSIFreq};
+    #[cfg(feature = "py32f030")]
+    use super::{LSEMode, PLLSrc};
 
     pub(super) const HSI_DEFAULT: u32 = 24_000_000; // Hz
+    pub(super) const LSI_FREQ: u32 = 32_768; // Hz
+    #[cfg(feature = "py32f030")]
+    pub(super) const LSE_FREQ: u32 = 32_768; // Hz
 
     #[allow(clippy::upper_case_acronyms)]
+    #[derive(Clone, Copy)]
     pub(super) enum SysClkSource {
-        // PLL(Option<(u32, super::HSEBypassMode)>),
         HSISYS(HSIFreq),
 
         /// High-speed external clock(freq,bypassed)
         HSE(u32, HSEBypassMode),
-        // LSI,
-        // LSE(u32),
-    }
-
-    fn get_hsi_sel_freq(c_src: &SysClkSource) -> u32 {
-        if let SysClkSource::HSISYS(fs) = c_src {
-            match fs {
-                HSIFreq::Freq4mhz => 4_000_000,
-                HSIFreq::Freq8mhz => 8_000_000,
-                HSIFreq::Freq16mhz => 16_000_000,
-                HSIFreq::Freq22_12mhz => 22_120_000,
-                HSIFreq::Freq24mhz => 24_000_000,
-            }
-        } else {
-            4_000_000
+        #[cfg(feature = "py32f030")]
+        PLL {
+            src: PLLSrc,
+        },
+        LSI,
+        #[cfg(feature = "py32f030")]
+        LSE(LSEMode),
+    }
+
+    fn get_hsi_sel_freq(fs: &HSIFreq) -> u32 {
+        match fs {
+            HSIFreq::Freq4mhz => 4_000_000,
+            HSIFreq::Freq8mhz => 8_000_000,
+            HSIFreq::Freq16mhz => 16_000_000,
+            HSIFreq::Freq22_12mhz => 22_120_000,
+            HSIFreq::Freq24mhz => 24_000_000,
+        }
+    }
+
+    #[cfg(feature = "py32f030")]
+    pub(super) fn get_pll_input_freq(src: &PLLSrc) -> u32 {
+        match src {
+            PLLSrc::HSI(fs) => get_hsi_sel_freq(fs),
+            PLLSrc::HSE(freq, _) => freq.0,
         }
     }
 
     pub(super) fn get_freq(c_src: &SysClkSource) -> u32 {
-        // Select clock source based on user input and capability
-        // Highest selected frequency source available takes precedent.
         match c_src {
-            SysClkSource::HSISYS(_) => {
-                let hsi_freq = get_hsi_sel_freq(c_src);
-                hsi_freq
-            }
+            SysClkSource::HSISYS(fs) => get_hsi_sel_freq(fs),
             SysClkSource::HSE(freq, _) => *freq,
+            // The PLL doubles its input frequency
+            #[cfg(feature = "py32f030")]
+            SysClkSource::PLL { src } => get_pll_input_freq(src) * 2,
+            SysClkSource::LSI => LSI_FREQ,
+            #[cfg(feature = "py32f030")]
+            SysClkSource::LSE(_) => LSE_FREQ,
+        }
+    }
+
+    /// Feeds the clock source through the PLL
+    #[cfg(feature = "py32f030")]
+    pub(super) fn into_pll(c_src: SysClkSource) -> Result<SysClkSource, Error> {
+        match c_src {
+            SysClkSource::HSISYS(fs) => Ok(SysClkSource::PLL {
+                src: PLLSrc::HSI(fs),
+            }),
+            SysClkSource::HSE(freq, bypassed) => Ok(SysClkSource::PLL {
+                src: PLLSrc::HSE(crate::time::Hertz(freq), bypassed),
+            }),
+            _ => Err(Error::SysclkUnreachable),
         }
     }
 
+    #[cfg(any(feature = "py32f003", feature = "py32f002a", feature = "py32f002b"))]
+    pub(super) fn into_pll(_c_src: SysClkSource) -> Result<SysClkSource, Error> {
+        Err(Error::PllUnavailable)
+    }
+
     #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
-    fn hse_enable(rcc: &mut RCC, freq: u32, bypassed: &HSEBypassMode) {
+    fn hse_enable(rcc: &mut RCC, freq: u32, bypassed: &HSEBypassMode, css: bool) {
         let freq_bits = match freq {
             f if f >= 4_000_000 && f < 8_000_000 => 0b01,
             f if f >= 8_000_000 && f < 16_000_000 => 0b10,
@@ -224,94 +591,173 @@ mod inner {
         rcc.ecscr.modify(|_, w| w.hse_freq().bits(freq_bits));
         match bypassed {
             super::HSEBypassMode::NotBypassed => {
-                rcc.cr
-                    .modify(|_, w| w.csson().on().hseon().on().hsebyp().not_bypassed());
+                rcc.cr.modify(|_, w| w.hseon().on().hsebyp().not_bypassed());
             }
             super::HSEBypassMode::Bypassed => {
-                rcc.cr
-                    .modify(|_, w| w.csson().on().hseon().on().hsebyp().bypassed());
+                rcc.cr.modify(|_, w| w.hseon().on().hsebyp().bypassed());
             }
         }
         while !rcc.cr.read().hserdy().bit_is_set() {}
+        rcc.cr.modify(|_, w| w.csson().bit(css));
     }
 
     #[cfg(feature = "py32f002b")]
-    fn hse_enable(rcc: &mut RCC) {
-        // PY32F002B HSE only support the bypass mode
+    fn hse_enable(rcc: &mut RCC, _freq: u32, _bypassed: &HSEBypassMode, _css: bool) {
+        // PY32F002B HSE only support the bypass mode, which has to be selected before HSEON
         rcc.cr.modify(|_, w| w.hsebyp().bypassed());
+        rcc.cr.modify(|_, w| w.hseon().on());
+        while !rcc.cr.read().hserdy().bit_is_set() {}
     }
 
+    /// Factory trim values of the HSI, one word for each `HSIFreq`
+    const HSI_TRIM_BASE: u32 = 0x1FFF_0F00;
+    pub(super) const HSI_TRIM_MAX: u16 = 0x1FFF;
+
     fn hsi_enable(rcc: &mut RCC, fs: &HSIFreq) {
-        rcc.icscr
-            .modify(|_, w| w.hsi_fs().variant(fs.clone().into()));
+        // NOTE(unsafe) the configuration bytes are always readable
+        let trim = unsafe { ptr::read((HSI_TRIM_BASE + *fs as u32 * 4) as *const u32) } as u16;
+        rcc.icscr.modify(|_, w| unsafe {
+            w.hsi_fs()
+                .variant(fs.clone().into())
+                .hsi_trim()
+                .bits(trim & HSI_TRIM_MAX)
+        });
         rcc.cr.modify(|_, w| w.hsion().set_bit());
         while rcc.cr.read().hsirdy().bit_is_clear() {}
     }
 
-    pub(super) fn enable_clock(rcc: &mut RCC, c_src: &SysClkSource) {
-        // Enable the requested clock
-        match c_src {
-            SysClkSource::HSE(freq, bypassed) => {
-                #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
-                hse_enable(rcc, *freq, bypassed);
-                #[cfg(feature = "py32f002b")]
-                if let HSEBypassMode::Bypassed = bypassed {
-                    hse_enable(rcc);
-                } else {
-                    panic!("HSE in PY32F002B must be bypassed")
-                }
+    pub(super) fn lsi_enable(rcc: &mut RCC) {
+        rcc.csr.modify(|_, w| w.lsion().set_bit());
+        while rcc.csr.read().lsirdy().bit_is_clear() {}
+    }
+
+    #[cfg(feature = "py32f030")]
+    pub(super) fn lse_enable(rcc: &mut RCC, mode: &LSEMode) {
+        // The LSE is part of the write protected backup domain
+        rcc.apbenr1.modify(|_, w| w.pwren().set_bit());
+        let pwr = unsafe { &(*crate::pac::PWR::ptr()) };
+        pwr.cr1.modify(|_, w| w.dbp().set_bit());
+
+        match mode {
+            LSEMode::Crystal(drive) => {
+                rcc.ecscr.modify(|_, w| w.lse_driver().bits(*drive as u8));
+                rcc.bdcr
+                    .modify(|_, w| w.lsebyp().clear_bit().lseon().set_bit());
             }
-            SysClkSource::HSISYS(fs) => {
-                hsi_enable(rcc, fs);
+            LSEMode::Bypassed => {
+                rcc.bdcr
+                    .modify(|_, w| w.lsebyp().set_bit().lseon().set_bit());
             }
         }
+        while rcc.bdcr.read().lserdy().bit_is_clear() {}
     }
 
     #[cfg(feature = "py32f030")]
-    pub(super) fn enable_pll(rcc: &mut RCC, c_src: &SysClkSource, ppre_bits: u8, hpre_bits: u8) {
-        let pllsrc_bit = match c_src {
-            SysClkSource::HSISYS(_) => false,
-            SysClkSource::HSE(_, _) => true,
+    fn pll_enable(rcc: &mut RCC, src: &PLLSrc, css: bool) {
+        // The PLL can only be configured while it is stopped, run from HSISYS meanwhile
+        if rcc.cfgr.read().sws().bits() == u8::from(SW_A::Pll) {
+            rcc.cr.modify(|_, w| w.hsion().set_bit());
+            while rcc.cr.read().hsirdy().bit_is_clear() {}
+            rcc.cfgr.modify(|_, w| w.sw().hsisys());
+            while rcc.cfgr.read().sws().bits() != u8::from(SW_A::Hsisys) {}
+        }
+        rcc.cr.modify(|_, w| w.pllon().clear_bit());
+        while rcc.cr.read().pllrdy().bit_is_set() {}
+
+        let pllsrc_bit = match src {
+            PLLSrc::HSI(fs) => {
+                hsi_enable(rcc, fs);
+                false
+            }
+            PLLSrc::HSE(freq, bypassed) => {
+                hse_enable(rcc, freq.0, bypassed, css);
+                true
+            }
         };
         rcc.pllcfgr.modify(|_, w| w.pllsrc().bit(pllsrc_bit));
 
         rcc.cr.modify(|_, w| w.pllon().set_bit());
         while rcc.cr.read().pllrdy().bit_is_clear() {}
+    }
 
-        rcc.cfgr
-            .modify(|_, w| unsafe { w.ppre().bits(ppre_bits).hpre().bits(hpre_bits).sw().pll() });
+    pub(super) fn enable_clock(rcc: &mut RCC, c_src: &SysClkSource, css: bool) {
+        // Enable the requested clock
+        match c_src {
+            SysClkSource::HSE(freq, bypassed) => hse_enable(rcc, *freq, bypassed, css),
+            SysClkSource::HSISYS(fs) => hsi_enable(rcc, fs),
+            #[cfg(feature = "py32f030")]
+            SysClkSource::PLL { src } => pll_enable(rcc, src, css),
+            SysClkSource::LSI => lsi_enable(rcc),
+            #[cfg(feature = "py32f030")]
+            SysClkSource::LSE(mode) => lse_enable(rcc, mode),
+        }
     }
 
-    #[cfg(any(feature = "py32f003", feature = "py32f002a", feature = "py32f002b"))]
-    #[inline(always)]
-    pub(super) fn enable_pll(
-        _rcc: &mut RCC,
-        _c_src: &SysClkSource,
-        _ppre_bits: u8,
-        _hpre_bits: u8,
-    ) {
-        panic!("PLL only supported on py32f030. Please select a sysclk and clock source combination so as to not require the use of PLL")
+    /// Stops the PLL and HSE if the clock source does not use them
+    #[cfg_attr(feature = "py32f002b", allow(unused_variables))]
+    pub(super) fn disable_unused(rcc: &mut RCC, c_src: &SysClkSource) {
+        #[cfg(feature = "py32f030")]
+        if !matches!(c_src, SysClkSource::PLL { .. }) {
+            rcc.cr.modify(|_, w| w.pllon().clear_bit());
+        }
+
+        #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+        {
+            let hse_used = match c_src {
+                SysClkSource::HSE(_, _) => true,
+                #[cfg(feature = "py32f030")]
+                SysClkSource::PLL {
+                    src: PLLSrc::HSE(_, _),
+                } => true,
+                _ => false,
+            };
+            if !hse_used {
+                rcc.cr
+                    .modify(|_, w| w.csson().clear_bit().hseon().clear_bit());
+            }
+        }
     }
 
     pub(super) fn get_sww(c_src: &SysClkSource) -> SW_A {
         match c_src {
             SysClkSource::HSISYS(_) => SW_A::Hsisys,
             SysClkSource::HSE(_, _) => SW_A::Hse,
+            #[cfg(feature = "py32f030")]
+            SysClkSource::PLL { .. } => SW_A::Pll,
+            SysClkSource::LSI => SW_A::Lsi,
+            #[cfg(feature = "py32f030")]
+            SysClkSource::LSE(_) => SW_A::Lse,
         }
     }
 }
 
 use self::inner::SysClkSource;
 
-pub struct CFGR {
+/// Clock configuration
+///
+/// Applied with `CFGR::freeze` at startup or `Rcc::reconfigure` at runtime.
+#[derive(Clone, Copy)]
+pub struct Config {
     hclk: Option<u32>,
     pclk: Option<u32>,
     sysclk: Option<u32>,
     clock_src: SysClkSource,
-    rcc: RCC,
+    css: bool,
 }
 
-impl CFGR {
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            hclk: None,
+            pclk: None,
+            sysclk: None,
+            clock_src: SysClkSource::HSISYS(HSIFreq::Freq24mhz),
+            css: false,
+        }
+    }
+}
+
+impl Config {
     #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
     pub fn hse<F>(mut self, freq: F, bypass: HSEBypassMode) -> Self
     where
@@ -334,6 +780,36 @@ impl CFGR {
         self
     }
 
+    /// Uses the PLL as sysclk source, which doubles the frequency of `src`
+    #[cfg(feature = "py32f030")]
+    pub fn pll(mut self, src: PLLSrc) -> Self {
+        self.clock_src = SysClkSource::PLL { src };
+        self
+    }
+
+    /// Uses the low-speed internal oscillator as sysclk source
+    pub fn lsi(mut self) -> Self {
+        self.clock_src = SysClkSource::LSI;
+        self
+    }
+
+    /// Uses the 32.768 kHz low-speed external oscillator as sysclk source
+    #[cfg(feature = "py32f030")]
+    pub fn lse(mut self, mode: LSEMode) -> Self {
+        self.clock_src = SysClkSource::LSE(mode);
+        self
+    }
+
+    /// Enables the clock security system of the HSE
+    ///
+    /// If the HSE fails the system clock is switched to HSISYS and a NMI is raised,
+    /// which has to be acknowledged with [`handle_css_nmi`].
+    #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+    pub fn css(mut self, enable: bool) -> Self {
+        self.css = enable;
+        self
+    }
+
     pub fn hclk<F>(mut self, freq: F) -> Self
     where
         F: Into<Hertz>,
@@ -358,49 +834,59 @@ impl CFGR {
         self
     }
 
-    pub fn freeze(mut self, flash: &mut crate::pac::FLASH) -> Rcc {
-        // Default to lowest frequency clock on all systems.
-        let sysclk = self.sysclk.unwrap_or(self::inner::HSI_DEFAULT);
-
-        // Select clock source based on user input and capability
-        // Highest selected frequency source available takes precedent.
-        let src_clk_freq = self::inner::get_freq(&self.clock_src);
+    /// Switches the clock tree to this configuration
+    ///
+    /// Everything is validated before the first register is touched, so on error
+    /// the clocks keep running as before.
+    fn apply(&self, rcc: &mut RCC, flash: &mut crate::pac::FLASH) -> Result<Clocks, Error> {
+        let mut clock_src = self.clock_src;
+        let src_clk_freq = self::inner::get_freq(&clock_src);
+        // HSI and HSE default to 24 MHz, the LSI, LSE and PLL keep their own frequency
+        let sysclk = self.sysclk.unwrap_or(match clock_src {
+            SysClkSource::HSISYS(_) | SysClkSource::HSE(..) => self::inner::HSI_DEFAULT,
+            _ => src_clk_freq,
+        });
 
-        let (pll_en, hsi_div_bits) = if sysclk == src_clk_freq {
-            (false, None)
+        let hsi_div_bits = if sysclk == src_clk_freq {
+            None
         } else if sysclk == src_clk_freq * 2 {
-            (true, None)
+            // Double the frequency with the PLL
+            clock_src = self::inner::into_pll(clock_src)?;
+            None
         } else {
-            if let SysClkSource::HSISYS(_) = self.clock_src {
-                let div = match src_clk_freq / sysclk {
-                    0 => unreachable!(),
-                    1 => 0b000,
-                    2 => 0b001,
-                    3..=5 => 0b010,
-                    6..=11 => 0b011,
-                    12..=23 => 0b100,
-                    24..=47 => 0b101,
-                    48..=95 => 0b110,
-                    _ => 0b111,
-                };
-                (false, Some(div))
-            } else {
-                unreachable!()
+            match clock_src {
+                SysClkSource::HSISYS(_) if sysclk != 0 && sysclk < src_clk_freq => {
+                    Some(match src_clk_freq / sysclk {
+                        1 => 0b000,
+                        2 => 0b001,
+                        3..=5 => 0b010,
+                        6..=11 => 0b011,
+                        12..=23 => 0b100,
+                        24..=47 => 0b101,
+                        48..=95 => 0b110,
+                        _ => 0b111,
+                    })
+                }
+                _ => return Err(Error::SysclkUnreachable),
             }
         };
 
-        let r_sysclk;
-        if let Some(div_bits) = hsi_div_bits {
-            r_sysclk = src_clk_freq / (1 << div_bits);
-            self.rcc.cr.modify(|_, w| w.hsidiv().bits(div_bits));
-        } else {
-            r_sysclk = sysclk;
+        #[cfg(feature = "py32f030")]
+        if let SysClkSource::PLL { src } = &clock_src {
+            let pll_input = self::inner::get_pll_input_freq(src);
+            if !(16_000_000..=24_000_000).contains(&pll_input) {
+                return Err(Error::PllInputOutOfRange);
+            }
         }
 
-        let hpre_bits = self
-            .hclk
-            .map(|hclk| match r_sysclk / hclk {
-                0 => unreachable!(),
+        let r_sysclk = match hsi_div_bits {
+            Some(div_bits) => src_clk_freq / (1 << div_bits),
+            None => sysclk,
+        };
+
+        let hpre_bits = match self.hclk {
+            Some(hclk) => match r_sysclk.checked_div(hclk).unwrap_or(0) {
+                0 => return Err(Error::HclkUnreachable),
                 1 => 0b0111,
                 2 => 0b1000,
                 3..=5 => 0b1001,
@@ -410,65 +896,177 @@ impl CFGR {
                 96..=191 => 0b1101,
                 192..=383 => 0b1110,
                 _ => 0b1111,
-            })
-            .unwrap_or(0b0111);
+            },
+            None => 0b0111,
+        };
 
         let hclk = r_sysclk / (1 << (hpre_bits - 0b0111));
 
-        let ppre_bits = self
-            .pclk
-            .map(|pclk| match hclk / pclk {
-                0 => unreachable!(),
+        let ppre_bits = match self.pclk {
+            Some(pclk) => match hclk.checked_div(pclk).unwrap_or(0) {
+                0 => return Err(Error::PclkUnreachable),
                 1 => 0b011,
                 2 => 0b100,
                 3..=5 => 0b101,
                 6..=11 => 0b110,
                 _ => 0b111,
-            })
-            .unwrap_or(0b011);
+            },
+            None => 0b011,
+        };
 
         let ppre: u8 = 1 << (ppre_bits - 0b011);
         let pclk = hclk / cast::u32(ppre);
 
-        // adjust flash wait states
-        flash.acr.write(|w| {
-            if r_sysclk <= 24_000_000 {
-                w.latency().ws0()
-            } else {
-                w.latency().ws1()
-            }
-        });
+        // Add flash wait states before speeding up, remove them only after slowing down
+        let wait_state = r_sysclk > 24_000_000;
+        if wait_state {
+            flash.acr.modify(|_, w| w.latency().ws1());
+        }
 
         // Enable the requested clock
-        self::inner::enable_clock(&mut self.rcc, &self.clock_src);
+        self::inner::enable_clock(rcc, &clock_src, self.css);
 
-        // Enable PLL
-        if pll_en {
-            self::inner::enable_pll(&mut self.rcc, &self.clock_src, ppre_bits, hpre_bits);
-        } else {
-            let sw_var = self::inner::get_sww(&self.clock_src);
-
-            // use HSISYS or HSE as source
-            self.rcc.cfgr.modify(|_, w| unsafe {
-                w.ppre()
-                    .bits(ppre_bits)
-                    .hpre()
-                    .bits(hpre_bits)
-                    .sw()
-                    .variant(sw_var)
-            });
+        rcc.cr
+            .modify(|_, w| w.hsidiv().bits(hsi_div_bits.unwrap_or(0b000)));
+
+        let sw_var = self::inner::get_sww(&clock_src);
+        let sw_bits = u8::from(sw_var);
+        rcc.cfgr.modify(|_, w| unsafe {
+            w.ppre()
+                .bits(ppre_bits)
+                .hpre()
+                .bits(hpre_bits)
+                .sw()
+                .variant(sw_var)
+        });
+        while rcc.cfgr.read().sws().bits() != sw_bits {}
+
+        if !wait_state {
+            flash.acr.modify(|_, w| w.latency().ws0());
         }
-        Rcc {
-            clocks: Clocks {
-                hclk: Hertz(hclk),
-                pclk: Hertz(pclk),
-                sysclk: Hertz(sysclk),
-            },
+
+        self::inner::disable_unused(rcc, &clock_src);
+
+        Ok(Clocks {
+            hclk: Hertz(hclk),
+            pclk: Hertz(pclk),
+            sysclk: Hertz(r_sysclk),
+            ppre,
+        })
+    }
+}
+
+pub struct CFGR {
+    config: Config,
+    rcc: RCC,
+}
+
+impl CFGR {
+    #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+    pub fn hse<F>(mut self, freq: F, bypass: HSEBypassMode) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.config = self.config.hse(freq, bypass);
+        self
+    }
+    #[cfg(feature = "py32f002b")]
+    pub fn hse_bypass<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.config = self.config.hse_bypass(freq);
+        self
+    }
+
+    pub fn hsi(mut self, fs: HSIFreq) -> Self {
+        self.config = self.config.hsi(fs);
+        self
+    }
+
+    /// Uses the PLL as sysclk source, which doubles the frequency of `src`
+    #[cfg(feature = "py32f030")]
+    pub fn pll(mut self, src: PLLSrc) -> Self {
+        self.config = self.config.pll(src);
+        self
+    }
+
+    /// Uses the low-speed internal oscillator as sysclk source
+    pub fn lsi(mut self) -> Self {
+        self.config = self.config.lsi();
+        self
+    }
+
+    /// Uses the 32.768 kHz low-speed external oscillator as sysclk source
+    #[cfg(feature = "py32f030")]
+    pub fn lse(mut self, mode: LSEMode) -> Self {
+        self.config = self.config.lse(mode);
+        self
+    }
+
+    /// Enables the clock security system of the HSE, see [`Config::css`]
+    #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+    pub fn css(mut self, enable: bool) -> Self {
+        self.config = self.config.css(enable);
+        self
+    }
+
+    pub fn hclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.config = self.config.hclk(freq);
+        self
+    }
+
+    pub fn pclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.config = self.config.pclk(freq);
+        self
+    }
+
+    /// Sets the system clock frequency, defaults to 24 MHz for the HSI and HSE
+    pub fn sysclk<F>(mut self, freq: F) -> Self
+    where
+        F: Into<Hertz>,
+    {
+        self.config = self.config.sysclk(freq);
+        self
+    }
+
+    /// Applies the clock configuration
+    ///
+    /// Fails if the requested frequencies can not be derived from the selected clock source.
+    pub fn freeze(mut self, flash: &mut crate::pac::FLASH) -> Result<Rcc, Error> {
+        let clocks = self.config.apply(&mut self.rcc, flash)?;
+        Ok(Rcc {
+            clocks,
             regs: self.rcc,
-        }
+        })
     }
 }
 
+/// Acknowledges a failure of the HSE detected by the clock security system
+///
+/// Call this from the `NMI` exception handler. Returns `true` if the NMI was raised
+/// by the clock security system. The HSE is switched off by hardware, this makes
+/// sure the system keeps running from HSISYS. The frozen `Clocks` no longer apply.
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+pub fn handle_css_nmi() -> bool {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    if rcc.cifr.read().cssf().bit_is_clear() {
+        return false;
+    }
+    rcc.cicr.write(|w| w.cssc().set_bit());
+
+    rcc.cr.modify(|_, w| w.hsion().set_bit());
+    while rcc.cr.read().hsirdy().bit_is_clear() {}
+    rcc.cfgr.modify(|_, w| w.sw().hsisys());
+    true
+}
+
 /// Frozen clock frequencies
 ///
 /// The existence of this value indicates that the clock configuration can no longer be changed
@@ -477,6 +1075,7 @@ pub struct Clocks {
     hclk: Hertz,
     pclk: Hertz,
     sysclk: Hertz,
+    ppre: u8,
 }
 
 impl Clocks {
@@ -494,4 +1093,24 @@ impl Clocks {
     pub fn sysclk(&self) -> Hertz {
         self.sysclk
     }
+
+    /// Returns the frequency of the timer kernel clock (TIMCLK)
+    ///
+    /// The timers run at twice the APB frequency whenever the APB prescaler divides.
+    pub fn timer_clk(&self) -> Hertz {
+        if self.ppre == 1 {
+            self.pclk
+        } else {
+            Hertz(self.pclk.0 * 2)
+        }
+    }
+}
+
+/// Drivers deriving their timing from the bus clocks
+///
+/// After [`Rcc::reconfigure`] the drivers recompute their prescalers with the new `Clocks`,
+/// keeping the baud rate or bus speed they were created with.
+pub trait ClockDependent {
+    /// Recomputes the timing for `clocks`
+    fn update_clocks(&mut self, clocks: &Clocks);
 }
@@ -0,0 +1,237 @@
+use super::{Clocks, Rcc};
+use crate::pac::RCC;
+use crate::time::Hertz;
+
+/// AMBA High-performance Bus (AHB)
+pub struct AHB;
+/// Advanced Peripheral Bus 1 (APB1)
+pub struct APB1;
+/// Advanced Peripheral Bus 2 (APB2)
+pub struct APB2;
+/// Single cycle I/O port bus (IOP)
+pub struct IOP;
+
+/// Bus a peripheral is attached to
+pub trait RccBus {
+    type Bus;
+}
+
+/// Frequency of the clock feeding a bus or peripheral
+pub trait GetBusFreq {
+    /// Returns the bus clock
+    fn get_frequency(clocks: &Clocks) -> Hertz;
+
+    /// Returns the clock of timers attached to the bus
+    fn get_timer_frequency(clocks: &Clocks) -> Hertz {
+        Self::get_frequency(clocks)
+    }
+}
+
+impl GetBusFreq for AHB {
+    fn get_frequency(clocks: &Clocks) -> Hertz {
+        clocks.hclk()
+    }
+}
+
+impl GetBusFreq for IOP {
+    fn get_frequency(clocks: &Clocks) -> Hertz {
+        clocks.hclk()
+    }
+}
+
+macro_rules! bus_apb {
+    ($($APB:ident,)+) => {
+        $(
+            impl GetBusFreq for $APB {
+                fn get_frequency(clocks: &Clocks) -> Hertz {
+                    clocks.pclk()
+                }
+
+                fn get_timer_frequency(clocks: &Clocks) -> Hertz {
+                    clocks.timer_clk()
+                }
+            }
+        )+
+    };
+}
+
+bus_apb!(APB1, APB2,);
+
+impl<T> GetBusFreq for T
+where
+    T: RccBus,
+    T::Bus: GetBusFreq,
+{
+    fn get_frequency(clocks: &Clocks) -> Hertz {
+        T::Bus::get_frequency(clocks)
+    }
+
+    fn get_timer_frequency(clocks: &Clocks) -> Hertz {
+        T::Bus::get_timer_frequency(clocks)
+    }
+}
+
+/// Clock gating of a peripheral
+pub trait Enable: RccBus {
+    /// Enables the clock of the peripheral
+    fn enable(_rcc: &mut Rcc) {
+        unsafe { Self::enable_unchecked() }
+    }
+
+    /// Disables the clock of the peripheral
+    fn disable(_rcc: &mut Rcc) {
+        unsafe { Self::disable_unchecked() }
+    }
+
+    /// Keeps the peripheral clocked in sleep mode
+    fn enable_in_sleep_mode(_rcc: &mut Rcc) {
+        unsafe { Self::enable_in_sleep_mode_unchecked() }
+    }
+
+    /// Stops the clock of the peripheral in sleep mode
+    fn disable_in_sleep_mode(_rcc: &mut Rcc) {
+        unsafe { Self::disable_in_sleep_mode_unchecked() }
+    }
+
+    /// Returns `true` if the clock of the peripheral is enabled
+    fn is_enabled() -> bool;
+
+    /// Enables the clock of the peripheral without a reference to `Rcc`
+    ///
+    /// # Safety
+    ///
+    /// The read-modify-write of the RCC register must not race with other accesses.
+    unsafe fn enable_unchecked();
+
+    /// Disables the clock of the peripheral without a reference to `Rcc`
+    ///
+    /// # Safety
+    ///
+    /// The read-modify-write of the RCC register must not race with other accesses.
+    unsafe fn disable_unchecked();
+
+    /// Keeps the peripheral clocked in sleep mode without a reference to `Rcc`
+    ///
+    /// # Safety
+    ///
+    /// The read-modify-write of the RCC register must not race with other accesses.
+    unsafe fn enable_in_sleep_mode_unchecked();
+
+    /// Stops the clock of the peripheral in sleep mode without a reference to `Rcc`
+    ///
+    /// # Safety
+    ///
+    /// The read-modify-write of the RCC register must not race with other accesses.
+    unsafe fn disable_in_sleep_mode_unchecked();
+}
+
+/// Reset of a peripheral
+pub trait Reset: RccBus {
+    /// Resets all registers of the peripheral
+    fn reset(_rcc: &mut Rcc) {
+        unsafe { Self::reset_unchecked() }
+    }
+
+    /// Resets the peripheral without a reference to `Rcc`
+    ///
+    /// # Safety
+    ///
+    /// The read-modify-write of the RCC register must not race with other accesses.
+    unsafe fn reset_unchecked();
+}
+
+macro_rules! bus {
+    ($($PER:ident => ($bus:ident, $enr:ident, $smenr:ident, $en:ident, $smen:ident $(, $rstr:ident, $rst:ident)?),)+) => {
+        $(
+            impl RccBus for crate::pac::$PER {
+                type Bus = $bus;
+            }
+
+            impl Enable for crate::pac::$PER {
+                fn is_enabled() -> bool {
+                    // NOTE(unsafe) atomic read with no side effects
+                    unsafe { (*RCC::ptr()).$enr.read().$en().bit_is_set() }
+                }
+
+                unsafe fn enable_unchecked() {
+                    (*RCC::ptr()).$enr.modify(|_, w| w.$en().set_bit());
+                }
+
+                unsafe fn disable_unchecked() {
+                    (*RCC::ptr()).$enr.modify(|_, w| w.$en().clear_bit());
+                }
+
+                unsafe fn enable_in_sleep_mode_unchecked() {
+                    (*RCC::ptr()).$smenr.modify(|_, w| w.$smen().set_bit());
+                }
+
+                unsafe fn disable_in_sleep_mode_unchecked() {
+                    (*RCC::ptr()).$smenr.modify(|_, w| w.$smen().clear_bit());
+                }
+            }
+
+            $(
+                impl Reset for crate::pac::$PER {
+                    unsafe fn reset_unchecked() {
+                        (*RCC::ptr()).$rstr.modify(|_, w| w.$rst().set_bit());
+                        (*RCC::ptr()).$rstr.modify(|_, w| w.$rst().clear_bit());
+                    }
+                }
+            )?
+        )+
+    };
+}
+
+bus! {
+    FLASH => (AHB, ahbenr, ahbsmenr, flashen, flashsmen),
+    CRC => (AHB, ahbenr, ahbsmenr, crcen, crcsmen, ahbrstr, crcrst),
+
+    GPIOA => (IOP, iopenr, iopsmenr, gpioaen, gpioasmen, ioprstr, gpioarst),
+    GPIOB => (IOP, iopenr, iopsmenr, gpioben, gpiobsmen, ioprstr, gpiobrst),
+
+    WWDG => (APB1, apbenr1, apbsmenr1, wwdgen, wwdgsmen),
+    I2C => (APB1, apbenr1, apbsmenr1, i2cen, i2csmen, apbrstr1, i2crst),
+    DBG => (APB1, apbenr1, apbsmenr1, dbgen, dbgsmen, apbrstr1, dbgrst),
+    PWR => (APB1, apbenr1, apbsmenr1, pwren, pwrsmen, apbrstr1, pwrrst),
+    LPTIM => (APB1, apbenr1, apbsmenr1, lptimen, lptimsmen, apbrstr1, lptimrst),
+
+    SYSCFG => (APB2, apbenr2, apbsmenr2, syscfgen, syscfgsmen, apbrstr2, syscfgrst),
+    TIM1 => (APB2, apbenr2, apbsmenr2, tim1en, tim1smen, apbrstr2, tim1rst),
+    SPI1 => (APB2, apbenr2, apbsmenr2, spi1en, spi1smen, apbrstr2, spi1rst),
+    USART1 => (APB2, apbenr2, apbsmenr2, usart1en, usart1smen, apbrstr2, usart1rst),
+    ADC => (APB2, apbenr2, apbsmenr2, adcen, adcsmen, apbrstr2, adcrst),
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+bus! {
+    DMA => (AHB, ahbenr, ahbsmenr, dmaen, dmasmen, ahbrstr, dmarst),
+
+    TIM3 => (APB1, apbenr1, apbsmenr1, tim3en, tim3smen, apbrstr1, tim3rst),
+    USART2 => (APB1, apbenr1, apbsmenr1, usart2en, usart2smen, apbrstr1, usart2rst),
+
+    TIM17 => (APB2, apbenr2, apbsmenr2, tim17en, tim17smen, apbrstr2, tim17rst),
+    COMP1 => (APB2, apbenr2, apbsmenr2, comp1en, comp1smen, apbrstr2, comp1rst),
+    COMP2 => (APB2, apbenr2, apbsmenr2, comp2en, comp2smen, apbrstr2, comp2rst),
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+bus! {
+    GPIOF => (IOP, iopenr, iopsmenr, gpiofen, gpiofsmen, ioprstr, gpiofrst),
+
+    TIM16 => (APB2, apbenr2, apbsmenr2, tim16en, tim16smen, apbrstr2, tim16rst),
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+bus! {
+    TIM14 => (APB2, apbenr2, apbsmenr2, tim14en, tim14smen, apbrstr2, tim14rst),
+}
+
+#[cfg(feature = "py32f002b")]
+bus! {
+    GPIOC => (IOP, iopenr, iopsmenr, gpiocen, gpiocsmen, ioprstr, gpiocrst),
+}
+
+#[cfg(feature = "py32f030")]
+bus! {
+    SPI2 => (APB1, apbenr1, apbsmenr1, spi2en, spi2smen, apbrstr1, spi2rst),
+}
@@ -67,8 +67,11 @@ use core::{
 use embedded_hal::prelude::*;
 
 use crate::gpio::{gpioa::*, gpiob::*};
-use crate::gpio::{Alternate, AF1};
-use crate::{rcc::Rcc, time::Bps};
+use crate::gpio::{Alternate, Locked, AF1};
+use crate::{
+    rcc::{ClockDependent, Clocks, Enable, Rcc},
+    time::Bps,
+};
 
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
 use crate::gpio::{gpiof::*, AF0, AF8};
@@ -108,6 +111,9 @@ pub enum Event {
 pub trait TxPin<USART> {}
 pub trait RxPin<USART> {}
 
+impl<USART, PIN: TxPin<USART>> TxPin<USART> for Locked<PIN> {}
+impl<USART, PIN: RxPin<USART>> RxPin<USART> for Locked<PIN> {}
+
 /// Macro to implement `TxPin` / `RxPin` for a certain pin, using a certain
 /// alternative function and for a certain serial peripheral.
 macro_rules! impl_pins {
@@ -211,6 +217,7 @@ impl_pins!(
 pub struct Serial<USART, TXPIN, RXPIN> {
     usart: USART,
     pins: (TXPIN, RXPIN),
+    baud_rate: Bps,
 }
 
 // Common register
@@ -235,7 +242,7 @@ pub struct Tx<USART> {
 unsafe impl<USART> Send for Tx<USART> {}
 
 macro_rules! usart {
-    ($($USART:ident: ($usart:ident, $usarttx:ident, $usartrx:ident, $usartXen:ident, $apbenr:ident),)+) => {
+    ($($USART:ident: ($usart:ident, $usarttx:ident, $usartrx:ident),)+) => {
         $(
             use crate::pac::$USART;
             impl<TXPIN, RXPIN> Serial<$USART, TXPIN, RXPIN>
@@ -246,8 +253,8 @@ macro_rules! usart {
                 /// Creates a new serial instance
                 pub fn $usart(usart: $USART, pins: (TXPIN, RXPIN), baud_rate: Bps, rcc: &mut Rcc) -> Self
                 {
-                    let mut serial = Serial { usart, pins };
-                    serial.configure(baud_rate, rcc);
+                    let mut serial = Serial { usart, pins, baud_rate };
+                    serial.configure(rcc);
                     // Enable transmission and receiving
                     serial.usart.cr1.modify(|_, w| w.te().set_bit().re().set_bit().ue().set_bit());
                     serial
@@ -262,8 +269,8 @@ macro_rules! usart {
                 pub fn $usarttx(usart: $USART, txpin: TXPIN, baud_rate: Bps, rcc: &mut Rcc) -> Self
                 {
                     let rxpin = ();
-                    let mut serial = Serial { usart, pins: (txpin, rxpin) };
-                    serial.configure(baud_rate, rcc);
+                    let mut serial = Serial { usart, pins: (txpin, rxpin), baud_rate };
+                    serial.configure(rcc);
                     // Enable transmission
                     serial.usart.cr1.modify(|_, w| w.te().set_bit().ue().set_bit());
                     serial
@@ -278,8 +285,8 @@ macro_rules! usart {
                 pub fn $usartrx(usart: $USART, rxpin: RXPIN, baud_rate: Bps, rcc: &mut Rcc) -> Self
                 {
                     let txpin = ();
-                    let mut serial = Serial { usart, pins: (txpin, rxpin) };
-                    serial.configure(baud_rate, rcc);
+                    let mut serial = Serial { usart, pins: (txpin, rxpin), baud_rate };
+                    serial.configure(rcc);
                     // Enable receiving
                     serial.usart.cr1.modify(|_, w| w.re().set_bit().ue().set_bit());
                     serial
@@ -287,19 +294,23 @@ macro_rules! usart {
             }
 
             impl<TXPIN, RXPIN> Serial<$USART, TXPIN, RXPIN> {
-                fn configure(&mut self, baud_rate: Bps, rcc: &mut Rcc) {
+                fn configure(&mut self, rcc: &mut Rcc) {
                     // Enable clock for USART
-                    rcc.regs.$apbenr.modify(|_, w| w.$usartXen().set_bit());
+                    $USART::enable(rcc);
 
-                    // Calculate correct baudrate divisor on the fly
-                    let brr = rcc.clocks.pclk().0 / baud_rate.0;
-                    self.usart.brr.write(|w| unsafe { w.bits(brr) });
+                    self.set_brr(&rcc.clocks);
 
                     // Reset other registers to disable advanced USART features
                     self.usart.cr2.reset();
                     self.usart.cr3.reset();
                 }
 
+                fn set_brr(&mut self, clocks: &Clocks) {
+                    // Calculate correct baudrate divisor on the fly
+                    let brr = clocks.pclk().0 / self.baud_rate.0;
+                    self.usart.brr.write(|w| unsafe { w.bits(brr) });
+                }
+
                 /// Starts listening for an interrupt event
                 pub fn listen(&mut self, event: Event) {
                     match event {
@@ -350,6 +361,14 @@ macro_rules! usart {
                     self.usart.sr.read().tc().bit_is_set()
                 }
             }
+
+            impl<TXPIN, RXPIN> ClockDependent for Serial<$USART, TXPIN, RXPIN> {
+                fn update_clocks(&mut self, clocks: &Clocks) {
+                    // Let an ongoing transmission finish with the old baud rate
+                    while !self.is_tx_complete() {}
+                    self.set_brr(clocks);
+                }
+            }
         )+
     }
 }
@@ -361,12 +380,12 @@ macro_rules! usart {
     feature = "py32f030",
 ))]
 usart! {
-    USART1: (usart1, usart1tx, usart1rx, usart1en, apbenr2),
+    USART1: (usart1, usart1tx, usart1rx),
 }
 
 #[cfg(any(feature = "py32f003", feature = "py32f030",))]
 usart! {
-    USART2: (usart2, usart2tx, usart2rx,usart2en, apbenr1),
+    USART2: (usart2, usart2tx, usart2rx),
 }
 
 impl<USART> embedded_hal::serial::Read<u8> for Rx<USART>
@@ -454,7 +473,12 @@ where
         )
     }
 
-    pub fn release(self) -> (USART, (TXPIN, RXPIN)) {
+    /// Stops the USART clock and releases the peripheral and pins
+    pub fn release(self) -> (USART, (TXPIN, RXPIN))
+    where
+        USART: Enable,
+    {
+        unsafe { USART::disable_unchecked() };
         (self.usart, self.pins)
     }
 }
@@ -15,7 +15,7 @@
 //!
 //! cortex_m::interrupt::free(|cs| {
 //!     let mut p = pac::Peripherals::take().unwrap();
-//!     let mut rcc = p.RCC.constrain().freeze(&mut p.FLASH);
+//!     let mut rcc = p.RCC.constrain().freeze(&mut p.FLASH).unwrap();
 //!
 //!     let gpioa = p.GPIOA.split(&mut rcc);
 //!
@@ -51,7 +51,7 @@ use crate::pac::SPI2;
 
 use crate::gpio::*;
 
-use crate::rcc::{Clocks, Rcc};
+use crate::rcc::{ClockDependent, Clocks, Enable, Rcc, Reset};
 
 use crate::time::Hertz;
 
@@ -77,6 +77,7 @@ pub enum Error {
 pub struct Spi<SPI, SCKPIN, MISOPIN, MOSIPIN, WIDTH> {
     spi: SPI,
     pins: (SCKPIN, MISOPIN, MOSIPIN),
+    speed: Hertz,
     _width: PhantomData<WIDTH>,
 }
 
@@ -84,6 +85,10 @@ pub trait SckPin<SPI> {}
 pub trait MisoPin<SPI> {}
 pub trait MosiPin<SPI> {}
 
+impl<SPI, PIN: SckPin<SPI>> SckPin<SPI> for Locked<PIN> {}
+impl<SPI, PIN: MisoPin<SPI>> MisoPin<SPI> for Locked<PIN> {}
+impl<SPI, PIN: MosiPin<SPI>> MosiPin<SPI> for Locked<PIN> {}
+
 macro_rules! spi_pins {
     ($($SPI:ident => {
         sck => [$($sck:ty),+ $(,)*],
@@ -189,7 +194,7 @@ spi_pins! {
 }
 
 macro_rules! spi {
-    ($($SPI:ident: ($spi:ident, $spiXen:ident, $spiXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($SPI:ident: $spi:ident,)+) => {
         $(
             impl<SCKPIN, MISOPIN, MOSIPIN> Spi<$SPI, SCKPIN, MISOPIN, MOSIPIN, EightBit> {
                 /// Creates a new spi instance
@@ -207,13 +212,12 @@ macro_rules! spi {
                     F: Into<Hertz>,
                 {
                     /* Enable clock for SPI */
-                    rcc.regs.$apbenr.modify(|_, w| w.$spiXen().set_bit());
+                    $SPI::enable(rcc);
 
                     /* Reset SPI */
-                    rcc.regs.$apbrstr.modify(|_, w| w.$spiXrst().set_bit());
-                    rcc.regs.$apbrstr.modify(|_, w| w.$spiXrst().clear_bit());
+                    $SPI::reset(rcc);
 
-                    Spi::<$SPI, SCKPIN, MISOPIN, MOSIPIN, EightBit> { spi, pins, _width: PhantomData }.spi_init(mode, speed, rcc.clocks).into_8bit_width()
+                    Spi::<$SPI, SCKPIN, MISOPIN, MOSIPIN, EightBit> { spi, pins, speed: speed.into(), _width: PhantomData }.spi_init(mode, rcc.clocks).into_8bit_width()
                 }
             }
         )+
@@ -221,11 +225,11 @@ macro_rules! spi {
 }
 
 spi! {
-    SPI1: (spi1, spi1en, spi1rst, apbenr2, apbrstr2),
+    SPI1: spi1,
 }
 #[cfg(feature = "py32f030")]
 spi! {
-    SPI2: (spi2, spi2en, spi2rst, apbenr1, apbrstr1),
+    SPI2: spi2,
 }
 
 // It's s needed for the impls, but rustc doesn't recognize that
@@ -236,24 +240,11 @@ impl<SPI, SCKPIN, MISOPIN, MOSIPIN, WIDTH> Spi<SPI, SCKPIN, MISOPIN, MOSIPIN, WI
 where
     SPI: Deref<Target = SpiRegisterBlock>,
 {
-    fn spi_init<F>(self, mode: Mode, speed: F, clocks: Clocks) -> Self
-    where
-        F: Into<Hertz>,
-    {
+    fn spi_init(self, mode: Mode, clocks: Clocks) -> Self {
         /* Make sure the SPI unit is disabled so we can configure it */
         self.spi.cr1.modify(|_, w| w.spe().clear_bit());
 
-        let br = match clocks.pclk().0 / speed.into().0 {
-            0 => unreachable!(),
-            1..=2 => 0b000,
-            3..=5 => 0b001,
-            6..=11 => 0b010,
-            12..=23 => 0b011,
-            24..=47 => 0b100,
-            48..=95 => 0b101,
-            96..=191 => 0b110,
-            _ => 0b111,
-        };
+        let br = self.baud_rate_bits(&clocks);
 
         // mstr: master configuration
         // lsbfirst: MSB first
@@ -304,6 +295,7 @@ where
         Spi {
             spi: self.spi,
             pins: self.pins,
+            speed: self.speed,
             _width: PhantomData,
         }
     }
@@ -324,10 +316,25 @@ where
         Spi {
             spi: self.spi,
             pins: self.pins,
+            speed: self.speed,
             _width: PhantomData,
         }
     }
 
+    fn baud_rate_bits(&self, clocks: &Clocks) -> u8 {
+        match clocks.pclk().0 / self.speed.0 {
+            // Clamped to the fastest rate if pclk was lowered below the configured speed
+            0..=2 => 0b000,
+            3..=5 => 0b001,
+            6..=11 => 0b010,
+            12..=23 => 0b011,
+            24..=47 => 0b100,
+            48..=95 => 0b101,
+            96..=191 => 0b110,
+            _ => 0b111,
+        }
+    }
+
     fn set_send_only(&mut self) {
         self.spi
             .cr1
@@ -397,11 +404,30 @@ where
         self.spi.dr().write(|w| w.dr().bits(byte as _));
     }
 
-    pub fn release(self) -> (SPI, (SCKPIN, MISOPIN, MOSIPIN)) {
+    /// Stops the SPI clock and releases the peripheral and pins
+    pub fn release(self) -> (SPI, (SCKPIN, MISOPIN, MOSIPIN))
+    where
+        SPI: Enable,
+    {
+        unsafe { SPI::disable_unchecked() };
         (self.spi, self.pins)
     }
 }
 
+impl<SPI, SCKPIN, MISOPIN, MOSIPIN, WIDTH> ClockDependent
+    for Spi<SPI, SCKPIN, MISOPIN, MOSIPIN, WIDTH>
+where
+    SPI: Deref<Target = SpiRegisterBlock>,
+{
+    fn update_clocks(&mut self, clocks: &Clocks) {
+        // The baud rate can only be changed between transfers with the SPI unit disabled
+        while self.spi.sr.read().bsy().bit_is_set() {}
+        let br = self.baud_rate_bits(clocks);
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        self.spi.cr1.modify(|_, w| w.br().bits(br).spe().set_bit());
+    }
+}
+
 impl<SPI, SCKPIN, MISOPIN, MOSIPIN> ::embedded_hal::blocking::spi::Transfer<u8>
     for Spi<SPI, SCKPIN, MISOPIN, MOSIPIN, EightBit>
 where
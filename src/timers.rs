@@ -15,24 +15,49 @@
 //!
 //! cortex_m::interrupt::free(|cs| {
 //!     let mut p = pac::Peripherals::take().unwrap();
-//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH);
+//!     let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
 //!
 //!     let gpioa = p.GPIOA.split(&mut rcc);
 //!
 //!     let mut led = gpioa.pa1.into_push_pull_pull_output(cs);
 //!
-//!     let mut timer = Timer::tim1(p.TIM1, Hertz(1), &mut rcc);
+//!     let mut timer = Timer::tim1(p.TIM1, Hertz(1), &mut rcc).unwrap();
 //!     loop {
 //!         led.toggle();
 //!         block!(timer.wait()).ok();
 //!     }
 //! });
 //! ```
+//!
+//! TIM1 and TIM3 can trigger each other, e.g. to start them in sync, to gate one
+//! with the other or to chain them into a 32 bit counter. Start a PWM on TIM3
+//! together with TIM1:
+//! ``` no_run
+//! let mut master = Timer::tim1(p.TIM1, Hertz(1_000), &mut rcc).unwrap();
+//! let (mut pwm, ch1) = Pwm::tim3(p.TIM3, pin, &mut rcc, 1.khz()).unwrap();
+//!
+//! // TIM3 stops until TIM1 is enabled
+//! pwm.set_slave_mode::<pac::TIM1>(SlaveMode::Trigger);
+//! master.set_master_mode(MasterMode::Enable);
+//! master.start(Hertz(1_000));
+//! ```
+//!
+//! Count the pulses of a flow meter on the external trigger input of TIM1 (only
+//! PY32F030 and PY32F002A route it to a pin, use the channel inputs elsewhere):
+//! ``` no_run
+//! let etr = gpioa.pa12.into_alternate_af2(cs);
+//! let config = CounterConfig::default().filter(0b0011);
+//! let mut counter = PulseCounter::tim1_etr(p.TIM1, etr, config, &mut rcc);
+//!
+//! let pulses = counter.count();
+//! counter.reset();
+//! ```
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::SYST;
 
-use crate::rcc::{Clocks, Rcc};
+use crate::rcc::{Clocks, Enable, Rcc, Reset};
 
+use crate::capture::Polarity;
 use crate::time::Hertz;
 use embedded_hal::timer::{CountDown, Periodic};
 use void::Void;
@@ -49,6 +74,34 @@ pub enum Event {
     TimeOut,
 }
 
+/// Timer errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The requested frequency can't be reached with the 16 bit prescaler and reload value
+    FrequencyOutOfRange,
+}
+
+/// Computes the prescaler and auto-reload values for an update rate of `freq`
+pub(crate) fn compute_psc_arr(tclk: Hertz, freq: Hertz) -> Result<(u16, u16), Error> {
+    let ticks = tclk.0.checked_div(freq.0).unwrap_or(0);
+    if ticks < 2 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+
+    let psc = cast::u16((ticks - 1) / (1 << 16)).map_err(|_| Error::FrequencyOutOfRange)?;
+    // Never exceeds 0xFFFF as the prescaler is rounded up
+    let arr = ticks / (u32::from(psc) + 1) - 1;
+    Ok((psc, arr as u16))
+}
+
+/// Computes the prescaler for a counter frequency of `freq`, rounding the frequency up
+pub(crate) fn compute_psc(tclk: Hertz, freq: Hertz) -> Result<u16, Error> {
+    if freq.0 == 0 || freq.0 > tclk.0 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+    cast::u16(tclk.0 / freq.0 - 1).map_err(|_| Error::FrequencyOutOfRange)
+}
+
 impl Timer<SYST> {
     /// Configures the SYST clock as a periodic count down timer
     pub fn syst<T>(mut syst: SYST, timeout: T, rcc: &Rcc) -> Self
@@ -113,7 +166,7 @@ impl CountDown for Timer<SYST> {
 impl Periodic for Timer<SYST> {}
 
 macro_rules! timers {
-    ($($TIM:ident: ($tim:ident, $timXen:ident, $timXrst:ident, $apbenr:ident, $apbrstr:ident),)+) => {
+    ($($TIM:ident: $tim:ident,)+) => {
         $(
             use crate::pac::$TIM;
             impl Timer<$TIM> {
@@ -121,22 +174,51 @@ macro_rules! timers {
                 // even if the `$TIM` are non overlapping (compare to the `free` function below
                 // which just works)
                 /// Configures a TIM peripheral as a periodic count down timer
-                pub fn $tim<T>(tim: $TIM, timeout: T, rcc: &mut Rcc) -> Self
+                pub fn $tim<T>(tim: $TIM, timeout: T, rcc: &mut Rcc) -> Result<Self, Error>
                 where
                     T: Into<Hertz>,
                 {
                     // enable and reset peripheral to a clean slate state
-                    rcc.regs.$apbenr.modify(|_, w| w.$timXen().set_bit());
-                    rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().set_bit());
-                    rcc.regs.$apbrstr.modify(|_, w| w.$timXrst().clear_bit());
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
 
                     let mut timer = Timer {
                         clocks: rcc.clocks,
                         tim,
                     };
-                    timer.start(timeout);
+                    timer.try_start(timeout)?;
+
+                    Ok(timer)
+                }
 
-                    timer
+                /// Starts the timer with a `timeout`, failing if it can't be reached
+                pub fn try_start<T>(&mut self, timeout: T) -> Result<(), Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let (psc, arr) = compute_psc_arr(self.clocks.timer_clk(), timeout.into())?;
+                    self.restart(psc, arr);
+                    Ok(())
+                }
+
+                fn restart(&mut self, psc: u16, arr: u16) {
+                    // pause
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // restart counter
+                    self.tim.cnt.reset();
+
+                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+                    self.tim.arr.write(|w| unsafe { w.bits(u32::from(arr)) });
+
+                    // start counter
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Returns the update rate actually reached with the current prescaler and reload value
+                pub fn frequency(&self) -> Hertz {
+                    let psc = u32::from(self.tim.psc.read().psc().bits()) + 1;
+                    let arr = self.tim.arr.read().bits() + 1;
+                    Hertz(self.clocks.timer_clk().0 / psc / arr)
                 }
 
                 /// Starts listening for an `event`
@@ -161,11 +243,10 @@ macro_rules! timers {
 
                 /// Releases the TIM peripheral
                 pub fn release(self) -> $TIM {
-                    let rcc = unsafe { &(*crate::pac::RCC::ptr()) };
                     // Pause counter
                     self.tim.cr1.modify(|_, w| w.cen().clear_bit());
                     // Disable timer
-                    rcc.$apbenr.modify(|_, w| w.$timXen().clear_bit());
+                    unsafe { $TIM::disable_unchecked() };
                     self.tim
                 }
 
@@ -173,38 +254,32 @@ macro_rules! timers {
                 pub fn clear_irq(&mut self) {
                     self.tim.sr.modify(|_, w| w.uif().clear_bit());
                 }
+
+                /// Returns the current counter value
+                pub fn counter(&self) -> u16 {
+                    self.tim.cnt.read().bits() as u16
+                }
             }
 
             impl CountDown for Timer<$TIM> {
                 type Time = Hertz;
 
                 /// Start the timer with a `timeout`
+                ///
+                /// A timeout out of range is clamped to the slowest or fastest update rate,
+                /// use `try_start` to detect it.
                 fn start<T>(&mut self, timeout: T)
                 where
                     T: Into<Hertz>,
                 {
-                    // pause
-                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
-                    // restart counter
-                    self.tim.cnt.reset();
-
-                    let frequency = timeout.into().0;
-                    // If pclk is prescaled from hclk, the frequency fed into the timers is doubled
-                    let tclk = if self.clocks.hclk().0 == self.clocks.pclk().0 {
-                        self.clocks.pclk().0
-                    } else {
-                        self.clocks.pclk().0 * 2
+                    let tclk = self.clocks.timer_clk();
+                    let timeout = timeout.into();
+                    let (psc, arr) = match compute_psc_arr(tclk, timeout) {
+                        Ok(values) => values,
+                        Err(_) if timeout.0 < tclk.0 / 2 => (u16::MAX, u16::MAX),
+                        Err(_) => (0, 1),
                     };
-                    let ticks = tclk / frequency;
-
-                    let psc = cast::u16((ticks - 1) / (1 << 16)).unwrap();
-                    self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
-
-                    let arr = cast::u16(ticks / cast::u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.bits(cast::u32(arr)) });
-
-                    // start counter
-                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                    self.restart(psc, arr);
                 }
 
                 /// Return `Ok` if the timer has wrapped
@@ -225,27 +300,337 @@ macro_rules! timers {
 }
 
 timers! {
-    TIM1: (tim1, tim1en, tim1rst, apbenr2, apbrstr2),
+    TIM1: tim1,
 }
 
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
 timers! {
-    TIM16: (tim16, tim16en, tim16rst, apbenr2, apbrstr2),
+    TIM16: tim16,
 }
 
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
 timers! {
-    TIM14: (tim14, tim14en, tim14rst, apbenr2, apbrstr2),
+    TIM14: tim14,
 }
 
 #[cfg(any(feature = "py32f030", feature = "py32f003"))]
 timers! {
-    TIM3: (tim3, tim3en, tim3rst, apbenr1, apbrstr1),
-    TIM17: (tim17, tim17en, tim17rst, apbenr2, apbrstr2),
+    TIM3: tim3,
+    TIM17: tim17,
+}
+
+/// Source of the trigger output (TRGO) of a master timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MasterMode {
+    /// Counter reset by software (`UG`) or the slave mode controller
+    Reset = 0b000,
+    /// Counter enable
+    Enable = 0b001,
+    /// Update event
+    Update = 0b010,
+    /// Pulse on each capture or compare match of channel 1
+    ComparePulse = 0b011,
+    /// Output compare reference of channel 1
+    Oc1Ref = 0b100,
+    /// Output compare reference of channel 2
+    Oc2Ref = 0b101,
+    /// Output compare reference of channel 3
+    Oc3Ref = 0b110,
+    /// Output compare reference of channel 4
+    Oc4Ref = 0b111,
+}
+
+/// Reaction of a slave timer to its trigger input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlaveMode {
+    /// Slave mode controller disabled
+    Disabled = 0b000,
+    /// The rising edge of the trigger resets the counter
+    Reset = 0b100,
+    /// The counter runs while the trigger is high
+    Gated = 0b101,
+    /// The rising edge of the trigger starts the counter
+    Trigger = 0b110,
+    /// The counter counts the rising edges of the trigger
+    ExternalClock = 0b111,
+}
+
+/// Trigger input of the slave mode controller of `TIM`
+pub trait TriggerSource<TIM> {
+    /// Value of the `TS` field selecting this input
+    const TS: u8;
+}
+
+/// Edges of the channel 1 input (TI1F_ED)
+pub struct Ti1Edge;
+/// Filtered channel 1 input (TI1FP1)
+pub struct Ti1Fp1;
+/// Filtered channel 2 input (TI2FP2)
+pub struct Ti2Fp2;
+/// Filtered external trigger input (ETRF)
+pub struct Etrf;
+
+macro_rules! trigger_source {
+    ($($SRC:ty: $SLAVE:ident = $ts:expr;)+) => {
+        $(
+            impl TriggerSource<$SLAVE> for $SRC {
+                const TS: u8 = $ts;
+            }
+        )+
+    };
+}
+
+macro_rules! master_slave {
+    ($($TIMER:ty: $TIM:ident,)+) => {
+        $(
+            impl $TIMER {
+                /// Selects the event driving the trigger output (TRGO)
+                pub fn set_master_mode(&mut self, mode: MasterMode) {
+                    self.tim.cr2.modify(|_, w| unsafe { w.mms().bits(mode as u8) });
+                }
+
+                /// Delays the trigger input of this timer so it starts in sync with
+                /// the timers it triggers (MSM)
+                pub fn set_master_slave_sync(&mut self, enable: bool) {
+                    self.tim.smcr.modify(|_, w| w.msm().bit(enable));
+                }
+
+                /// Selects the trigger input `S` and the reaction of the counter to it
+                ///
+                /// In trigger mode the counter is stopped and reset until the trigger starts it.
+                pub fn set_slave_mode<S>(&mut self, mode: SlaveMode)
+                where
+                    S: TriggerSource<$TIM>,
+                {
+                    if mode == SlaveMode::Trigger {
+                        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                        self.tim.cnt.reset();
+                    }
+                    self.tim.smcr.modify(|_, w| unsafe { w.ts().bits(S::TS).sms().bits(mode as u8) });
+                }
+            }
+        )+
+    };
+}
+
+trigger_source! {
+    Ti1Edge: TIM1 = 0b100;
+    Ti1Fp1: TIM1 = 0b101;
+    Ti2Fp2: TIM1 = 0b110;
+    Etrf: TIM1 = 0b111;
+}
+
+master_slave! {
+    Timer<TIM1>: TIM1,
+    crate::pwm::Pwm<TIM1>: TIM1,
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+trigger_source! {
+    // ITR2, trigger output of TIM3
+    TIM3: TIM1 = 0b010;
+    // ITR3, compare output of channel 1 of TIM17
+    TIM17: TIM1 = 0b011;
+    // ITR0, trigger output of TIM1
+    TIM1: TIM3 = 0b000;
+    // ITR3, compare output of channel 1 of TIM14
+    TIM14: TIM3 = 0b011;
+    Ti1Edge: TIM3 = 0b100;
+    Ti1Fp1: TIM3 = 0b101;
+    Ti2Fp2: TIM3 = 0b110;
+    Etrf: TIM3 = 0b111;
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+master_slave! {
+    Timer<TIM3>: TIM3,
+    crate::pwm::Pwm<TIM3>: TIM3,
+}
+
+/// Pulse counter clocked by an external signal
+pub struct PulseCounter<TIM, PIN> {
+    tim: TIM,
+    pin: PIN,
+}
+
+/// Division of the external trigger input before the edge detection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EtrPrescaler {
+    Div1 = 0b00,
+    Div2 = 0b01,
+    Div4 = 0b10,
+    Div8 = 0b11,
+}
+
+/// Pulse counter configuration
+#[derive(Clone, Copy, Debug)]
+pub struct CounterConfig {
+    polarity: Polarity,
+    prescaler: EtrPrescaler,
+    filter: u8,
+}
+
+impl Default for CounterConfig {
+    fn default() -> Self {
+        Self {
+            polarity: Polarity::Rising,
+            prescaler: EtrPrescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+impl CounterConfig {
+    /// Edges to count, the external trigger input can only count either rising or falling edges
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Prescaler of the external trigger input, the external signal must not toggle
+    /// faster than a quarter of the timer clock after the prescaler
+    pub fn prescaler(mut self, prescaler: EtrPrescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Digital input filter, `0` disables it (see the `ETF` and `ICxF` fields of the reference manual)
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter & 0xF;
+        self
+    }
+}
+
+macro_rules! pulse_counter {
+    ($($TIM:ident: ($tim_ti1:ident, $tim_ti2:ident),)+) => {
+        $(
+            impl<PIN> PulseCounter<$TIM, PIN> {
+                /// Counts the edges on the channel 1 `pin` (external clock mode 1)
+                ///
+                /// The prescaler of the configuration is ignored.
+                pub fn $tim_ti1(tim: $TIM, pin: PIN, config: CounterConfig, rcc: &mut Rcc) -> Self
+                where
+                    PIN: PinC1<$TIM>,
+                {
+                    let counter = Self::new(tim, pin, rcc);
+                    counter.tim.ccmr1_input().modify(|_, w| unsafe {
+                        w.cc1s().bits(0b01).ic1f().bits(config.filter)
+                    });
+                    // Both edges are counted through the edge detector (TI1F_ED)
+                    let ts = match config.polarity {
+                        Polarity::Rising => 0b101,
+                        Polarity::Falling => {
+                            counter.tim.ccer.modify(|_, w| w.cc1p().set_bit());
+                            0b101
+                        }
+                        Polarity::Both => 0b100,
+                    };
+                    counter.tim.smcr.modify(|_, w| unsafe { w.ts().bits(ts).sms().bits(0b111) });
+                    counter.start()
+                }
+
+                /// Counts the edges on the channel 2 `pin` (external clock mode 1)
+                ///
+                /// The prescaler of the configuration is ignored.
+                pub fn $tim_ti2(tim: $TIM, pin: PIN, config: CounterConfig, rcc: &mut Rcc) -> Self
+                where
+                    PIN: PinC2<$TIM>,
+                {
+                    let counter = Self::new(tim, pin, rcc);
+                    counter.tim.ccmr1_input().modify(|_, w| unsafe {
+                        w.cc2s().bits(0b01).ic2f().bits(config.filter)
+                    });
+                    let (p, np) = match config.polarity {
+                        Polarity::Rising => (false, false),
+                        Polarity::Falling => (true, false),
+                        Polarity::Both => (true, true),
+                    };
+                    counter.tim.ccer.modify(|_, w| w.cc2p().bit(p).cc2np().bit(np));
+                    counter.tim.smcr.modify(|_, w| unsafe { w.ts().bits(0b110).sms().bits(0b111) });
+                    counter.start()
+                }
+
+                fn new(tim: $TIM, pin: PIN, rcc: &mut Rcc) -> Self {
+                    // enable and reset peripheral to a clean slate state
+                    $TIM::enable(rcc);
+                    $TIM::reset(rcc);
+
+                    tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+                    PulseCounter { tim, pin }
+                }
+
+                fn start(self) -> Self {
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                    self
+                }
+
+                /// Returns the number of counted edges
+                pub fn count(&self) -> u16 {
+                    self.tim.cnt.read().bits() as u16
+                }
+
+                /// Resets the count to zero
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                }
+
+                /// Releases the TIM peripheral and the pin
+                pub fn release(self) -> ($TIM, PIN) {
+                    // Pause counter
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    // Disable timer
+                    unsafe { $TIM::disable_unchecked() };
+                    (self.tim, self.pin)
+                }
+            }
+        )+
+    };
+}
+
+pulse_counter! {
+    TIM1: (tim1_ti1, tim1_ti2),
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+pulse_counter! {
+    TIM3: (tim3_ti1, tim3_ti2),
+}
+
+// Only devices with an `EtrPin` get the external trigger constructor
+macro_rules! pulse_counter_etr {
+    ($($TIM:ident: $tim_etr:ident,)+) => {
+        $(
+            impl<PIN> PulseCounter<$TIM, PIN> {
+                /// Counts the edges on the external trigger `pin` (external clock mode 2)
+                pub fn $tim_etr(tim: $TIM, pin: PIN, config: CounterConfig, rcc: &mut Rcc) -> Self
+                where
+                    PIN: EtrPin<$TIM>,
+                {
+                    let counter = Self::new(tim, pin, rcc);
+                    counter.tim.smcr.modify(|_, w| unsafe {
+                        w.etp()
+                            .bit(config.polarity == Polarity::Falling)
+                            .etps()
+                            .bits(config.prescaler as u8)
+                            .etf()
+                            .bits(config.filter)
+                            .ece()
+                            .set_bit()
+                    });
+                    counter.start()
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f002a"))]
+pulse_counter_etr! {
+    TIM1: tim1_etr,
 }
 
 use crate::gpio::AF2;
-use crate::gpio::{gpioa::*, gpiob::*, Alternate};
+use crate::gpio::{gpioa::*, gpiob::*, Alternate, Locked};
 
 #[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
 use crate::gpio::{AF1, AF13, AF14};
@@ -268,6 +653,22 @@ pub trait PinC3<TIM> {}
 pub trait PinC3N<TIM> {}
 pub trait PinC4<TIM> {}
 
+// Break input marker trait
+pub trait BkinPin<TIM> {}
+
+// Locked pins keep their function
+impl<TIM, PIN: PinC1<TIM>> PinC1<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC1N<TIM>> PinC1N<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC2<TIM>> PinC2<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC2N<TIM>> PinC2N<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC3<TIM>> PinC3<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC3N<TIM>> PinC3N<TIM> for Locked<PIN> {}
+impl<TIM, PIN: PinC4<TIM>> PinC4<TIM> for Locked<PIN> {}
+impl<TIM, PIN: BkinPin<TIM>> BkinPin<TIM> for Locked<PIN> {}
+
+// External trigger input marker trait
+pub trait EtrPin<TIM> {}
+
 macro_rules! channel_impl {
     ( $( $TIM:ident, $PINC:ident, $PINX:ident, $MODE:ident<$AF:ident>; )+ ) => {
         $(
@@ -355,3 +756,86 @@ channel_impl!(
     TIM14, PinC1, PB5, Alternate<AF5>;
     TIM14, PinC1, PB7, Alternate<AF5>;
 );
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002a"))]
+channel_impl!(
+    TIM1, BkinPin, PA6, Alternate<AF2>;
+);
+
+#[cfg(any(feature = "py32f030", feature = "py32f003"))]
+channel_impl!(
+    TIM16, BkinPin, PB5, Alternate<AF2>;
+);
+
+#[cfg(feature = "py32f030")]
+channel_impl!(
+    TIM17, BkinPin, PB4, Alternate<AF5>;
+);
+
+#[cfg(any(feature = "py32f030", feature = "py32f002a"))]
+channel_impl!(
+    TIM1, EtrPin, PA12, Alternate<AF2>;
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCLK: Hertz = Hertz(48_000_000);
+
+    #[test]
+    fn psc_arr_rounds_period_down() {
+        for &freq in &[1, 10, 700, 733, 1_000, 50_000, 1_000_000, 24_000_000] {
+            let (psc, arr) = compute_psc_arr(TCLK, Hertz(freq)).unwrap();
+            let requested = TCLK.0 / freq;
+            let reached = (u32::from(psc) + 1) * (u32::from(arr) + 1);
+            assert!(reached <= requested, "{} Hz", freq);
+            assert!(requested - reached <= u32::from(psc), "{} Hz", freq);
+        }
+    }
+
+    #[test]
+    fn psc_arr_boundaries() {
+        assert_eq!(compute_psc_arr(TCLK, Hertz(1_000)), Ok((0, 47_999)));
+        // 65536 ticks still fit without prescaler
+        assert_eq!(compute_psc_arr(Hertz(65_536), Hertz(1)), Ok((0, 0xFFFF)));
+        assert_eq!(compute_psc_arr(Hertz(65_537), Hertz(1)), Ok((1, 32_767)));
+        assert_eq!(compute_psc_arr(TCLK, Hertz(1)), Ok((732, 65_483)));
+    }
+
+    #[test]
+    fn psc_arr_out_of_range() {
+        assert_eq!(
+            compute_psc_arr(TCLK, Hertz(0)),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            compute_psc_arr(TCLK, Hertz(TCLK.0 / 2 + 1)),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(compute_psc_arr(TCLK, TCLK), Err(Error::FrequencyOutOfRange));
+    }
+
+    #[test]
+    fn psc_counter_frequency() {
+        assert_eq!(compute_psc(TCLK, TCLK), Ok(0));
+        assert_eq!(compute_psc(TCLK, Hertz(1_000_000)), Ok(47));
+        // Rounded up to the next reachable frequency
+        assert_eq!(compute_psc(TCLK, Hertz(7_000_000)), Ok(5));
+        assert_eq!(compute_psc(TCLK, Hertz(733)), Ok(65_483));
+    }
+
+    #[test]
+    fn psc_out_of_range() {
+        assert_eq!(compute_psc(TCLK, Hertz(0)), Err(Error::FrequencyOutOfRange));
+        assert_eq!(
+            compute_psc(TCLK, Hertz(48_000_001)),
+            Err(Error::FrequencyOutOfRange)
+        );
+        // Capture at 100 Hz needs a prescaler of 480000
+        assert_eq!(
+            compute_psc(TCLK, Hertz(100)),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+}
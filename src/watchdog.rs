@@ -1,4 +1,4 @@
-//! API for the IWDG
+//! API for the IWDG and the WWDG
 //!
 //! You can activate the watchdog by calling `start` or the setting appropriate
 //! device option bit when programming.
@@ -13,16 +13,13 @@
 //!
 //! **Attention**:
 //!
-//! The IWDG runs on a separate 40kHz low-accuracy clock (30kHz-60kHz). You may
-//! want to some buffer in your interval.
+//! The IWDG runs on the separate 32.768kHz low-accuracy LSI clock, whose real
+//! frequency varies considerably between parts and over temperature. You may
+//! want to some buffer in your interval, or measure the LSI with `calibrate_lsi`
+//! and derive the timeout from the result with `timeout`.
 //!
 //! Per default the iwdg continues to run even when you stopped execution of code via a debugger.
-//! You may want to disable the watchdog when the cpu is stopped
-//!
-//! ``` ignore
-//! let dbgmcu = p.DBGMCU;
-//! dbgmcu.apb1_fz.modify(|_, w| w.dbg_iwdg_stop().set_bit());
-//! ```
+//! You may want to disable the watchdog when the cpu is stopped with `stop_on_debug`.
 //!
 //! # Example
 //! ``` no_run
@@ -30,26 +27,53 @@
 //!
 //! use crate::hal::pac;
 //! use crate::hal::prelude::*;
-//! use crate::hal:watchdog::Watchdog;
-//! use crate::hal:time::Hertz;
+//! use crate::hal::watchdog::Watchdog;
 //!
 //! let mut p = pac::Peripherals::take().unwrap();
+//! let mut rcc = p.RCC.configure().freeze(&mut p.FLASH).unwrap();
 //!
-//! let mut iwdg = Watchdog::new(p.iwdg);
-//! iwdg.start(Hertz(100));
+//! let mut iwdg = Watchdog::new(&mut rcc, p.IWDG);
+//! iwdg.stop_on_debug(&p.DBG, true, &mut rcc);
+//! iwdg.start(10.ms());
 //! loop {}
 //! // Whoops, got stuck, the watchdog issues a reset after 10 ms
 //! iwdg.feed();
 //! ```
+//!
+//! The WWDG runs from PCLK and additionally resets the microcontroller if it is
+//! fed too early, i.e. before the configured `window` has passed since the last
+//! `feed`. Its early wakeup interrupt fires shortly before the reset, which
+//! leaves time to save some state.
+//!
+//! ``` no_run
+//! use py32f0xx_hal as hal;
+//!
+//! use crate::hal::pac;
+//! use crate::hal::prelude::*;
+//! use crate::hal::watchdog::WindowWatchdog;
+//!
+//! let mut p = pac::Peripherals::take().unwrap();
+//! let mut rcc = p.RCC.configure().sysclk(24.mhz()).freeze(&mut p.FLASH).unwrap();
+//!
+//! let mut wwdg = WindowWatchdog::new(&mut rcc, p.WWDG);
+//! // Feeding is allowed between 10 ms and 40 ms after the last feed
+//! wwdg.set_window(10.ms());
+//! wwdg.listen();
+//! wwdg.start(40.ms());
+//! ```
 use embedded_hal::watchdog;
 
-use crate::rcc::Rcc;
-use crate::pac::IWDG;
-use crate::time::Hertz;
+use crate::pac::{DBG, IWDG, WWDG};
+use crate::rcc::{ClockDependent, Clocks, Enable, Rcc};
+use crate::time::{Hertz, MicroSeconds, MilliSeconds};
+
+/// Nominal frequency of the LSI clock
+pub const LSI_FREQ: Hertz = Hertz(32_768);
 
 /// Watchdog instance
 pub struct Watchdog {
     iwdg: IWDG,
+    lsi: Hertz,
 }
 
 impl watchdog::Watchdog for Watchdog {
@@ -67,26 +91,56 @@ pub struct IwdgTimeout {
     reload: u16,
 }
 
+impl IwdgTimeout {
+    /// Computes the configuration for a timeout of `ms` with an LSI running at `lsi`
+    ///
+    /// Timeouts beyond the range of the IWDG are clamped to the maximum.
+    pub fn from_ms(ms: MilliSeconds, lsi: Hertz) -> Self {
+        let ticks = u64::from(ms.0) * u64::from(lsi.0) / 1_000;
+        Self::from_lsi_ticks(ticks.min(u64::from(u32::MAX)) as u32)
+    }
+
+    /// Returns the timeout in milliseconds with an LSI running at `lsi`
+    pub fn to_ms(&self, lsi: Hertz) -> MilliSeconds {
+        let ticks = (u64::from(self.reload) + 1) * u64::from(prescaler(self.psc));
+        MilliSeconds((ticks * 1_000 / u64::from(lsi.0)) as u32)
+    }
+
+    /// Selects the smallest prescaler for which `ticks` LSI cycles fit into the
+    /// 12 bit reload value
+    fn from_lsi_ticks(ticks: u32) -> Self {
+        let mut psc = 0;
+        while psc < 6 && ticks > 0x1000 * prescaler(psc) {
+            psc += 1;
+        }
+        let reload = (ticks / prescaler(psc)).clamp(1, 0x1000) - 1;
+        IwdgTimeout {
+            psc,
+            reload: reload as u16,
+        }
+    }
+}
+
+/// Returns the LSI divider selected by the prescaler value `psc`
+fn prescaler(psc: u8) -> u32 {
+    // 0: /4, 1: /8, ... 6 and 7: /256
+    4 << psc.min(6)
+}
+
 impl From<Hertz> for IwdgTimeout {
     /// This converts the value so it's usable by the IWDG
     /// Due to conversion losses, the specified frequency is a maximum
     ///
-    /// It can also only represent values < 10000 Hertz
+    /// It can also only represent values < 8192 Hertz
     fn from(hz: Hertz) -> Self {
-        let mut time = 32_768 / 4 / hz.0;
-        let mut psc = 0;
-        let mut reload = 0;
-        while psc < 7 {
-            reload = time;
-            if reload < 0x1000 {
-                break;
-            }
-            psc += 1;
-            time /= 2;
-        }
-        // As we get an integer value, reload is always below 0xFFF
-        let reload = reload as u16;
-        IwdgTimeout { psc, reload }
+        Self::from_lsi_ticks(LSI_FREQ.0 / hz.0)
+    }
+}
+
+impl From<MilliSeconds> for IwdgTimeout {
+    /// Converts the timeout assuming the nominal LSI frequency
+    fn from(ms: MilliSeconds) -> Self {
+        Self::from_ms(ms, LSI_FREQ)
     }
 }
 
@@ -94,7 +148,99 @@ impl Watchdog {
     pub fn new(rcc: &mut Rcc, iwdg: IWDG) -> Self {
         rcc.regs.csr.modify(|_, w| w.lsion().on());
         while rcc.regs.csr.read().lsirdy().is_not_ready() {}
-        Self { iwdg }
+        Self {
+            iwdg,
+            lsi: LSI_FREQ,
+        }
+    }
+
+    /// Stops the watchdog while the core is halted by a debugger
+    ///
+    /// Enables the DBG clock, the freeze bits have no effect without it.
+    pub fn stop_on_debug(&self, dbg: &DBG, stop: bool, rcc: &mut Rcc) {
+        DBG::enable(rcc);
+        dbg.apb_fz1.modify(|_, w| w.dbg_iwdg_stop().bit(stop));
+    }
+
+    /// Returns the LSI frequency used to compute timeouts
+    pub fn lsi_frequency(&self) -> Hertz {
+        self.lsi
+    }
+
+    /// Computes the configuration for a timeout of `ms` from the (calibrated) LSI frequency
+    pub fn timeout(&self, ms: MilliSeconds) -> IwdgTimeout {
+        IwdgTimeout::from_ms(ms, self.lsi)
+    }
+
+    /// Returns the effective timeout of the running configuration
+    pub fn get_timeout(&self) -> MilliSeconds {
+        IwdgTimeout {
+            psc: self.iwdg.pr.read().pr().bits(),
+            reload: self.iwdg.rlr.read().rl().bits(),
+        }
+        .to_ms(self.lsi)
+    }
+}
+
+#[cfg(any(feature = "py32f030", feature = "py32f003", feature = "py32f002b"))]
+impl Watchdog {
+    /// Measures the real LSI frequency
+    ///
+    /// The LSI is routed through MCO to the input capture of TIM14 channel 1 and
+    /// its period is measured against the timer clock. The TIM14 peripheral is
+    /// reset afterwards, MCO is restored to its previous configuration.
+    ///
+    /// Only TIM14 can be used: it is the only timer with an input remap option
+    /// connecting MCO to its channel 1 internally, TIM16 would need an external
+    /// connection of the MCO pin to its channel 1 pin.
+    pub fn calibrate_lsi(&mut self, tim: &mut crate::pac::TIM14, rcc: &mut Rcc) -> Hertz {
+        use crate::pac::TIM14;
+        use crate::rcc::{MCODiv, MCOSrc, Reset};
+
+        // Number of LSI cycles between two captures
+        const IC_PRESCALER: u32 = 8;
+        const MEASUREMENTS: u32 = 8;
+
+        let cfgr = rcc.regs.cfgr.read();
+        let (mcosel, mcopre) = (cfgr.mcosel().bits(), cfgr.mcopre().bits());
+        rcc.configure_mco(MCOSrc::Lsi, MCODiv::NotDivided);
+
+        TIM14::enable(rcc);
+        TIM14::reset(rcc);
+
+        let tclk = rcc.clocks.timer_clk().0;
+
+        // TI1 connected to MCO, capture every 8th rising edge
+        tim.or.write(|w| unsafe { w.bits(0b11) });
+        tim.ccmr1_input()
+            .write(|w| unsafe { w.cc1s().bits(0b01).ic1psc().bits(0b11) });
+        tim.ccer.write(|w| w.cc1e().set_bit());
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        let mut capture = || {
+            tim.sr.write(|w| unsafe { w.bits(0) });
+            while tim.sr.read().cc1if().bit_is_clear() {}
+            tim.ccr1.read().bits() as u16
+        };
+
+        let mut last = capture();
+        let mut ticks = 0;
+        for _ in 0..MEASUREMENTS {
+            let now = capture();
+            ticks += u32::from(now.wrapping_sub(last));
+            last = now;
+        }
+
+        TIM14::reset(rcc);
+        rcc.regs
+            .cfgr
+            .modify(|_, w| unsafe { w.mcosel().bits(mcosel).mcopre().bits(mcopre) });
+
+        let lsi =
+            (u64::from(tclk) * u64::from(IC_PRESCALER * MEASUREMENTS) / u64::from(ticks)) as u32;
+        self.lsi = Hertz(lsi);
+        self.lsi
     }
 }
 
@@ -122,3 +268,221 @@ impl watchdog::WatchdogEnable for Watchdog {
         self.iwdg.kr.write(|w| w.key().reset());
     }
 }
+
+/// Window watchdog instance
+pub struct WindowWatchdog {
+    wwdg: WWDG,
+    pclk: Hertz,
+    window: MicroSeconds,
+    counter: u8,
+}
+
+impl WindowWatchdog {
+    pub fn new(rcc: &mut Rcc, wwdg: WWDG) -> Self {
+        WWDG::enable(rcc);
+        Self {
+            wwdg,
+            pclk: rcc.clocks.pclk(),
+            window: MicroSeconds(0),
+            counter: 0x7F,
+        }
+    }
+
+    /// Sets the time after a feed during which feeding again causes a reset
+    ///
+    /// Takes effect on the next `start`, a zero window disables the check.
+    pub fn set_window<T>(&mut self, window: T)
+    where
+        T: Into<MicroSeconds>,
+    {
+        self.window = window.into();
+    }
+
+    /// Enables the early wakeup interrupt, raised one tick before the reset
+    pub fn listen(&mut self) {
+        self.wwdg.cfr.modify(|_, w| w.ewi().set_bit());
+    }
+
+    /// Returns true if the early wakeup interrupt is pending
+    pub fn is_pending(&self) -> bool {
+        self.wwdg.sr.read().ewif().bit_is_set()
+    }
+
+    /// Clears the early wakeup interrupt flag
+    pub fn clear_irq(&mut self) {
+        self.wwdg.sr.write(|w| w.ewif().clear_bit());
+    }
+
+    /// Stops the watchdog while the core is halted by a debugger
+    ///
+    /// Enables the DBG clock, the freeze bits have no effect without it.
+    pub fn stop_on_debug(&self, dbg: &DBG, stop: bool, rcc: &mut Rcc) {
+        DBG::enable(rcc);
+        dbg.apb_fz1.modify(|_, w| w.dbg_wwdg_stop().bit(stop));
+    }
+
+    /// Returns the current value of the down counter
+    pub fn counter(&self) -> u8 {
+        self.wwdg.cr.read().t().bits()
+    }
+}
+
+/// Duration of one WWDG counter tick with the prescaler `wdgtb`, in nanoseconds
+fn wwdg_tick_ns(pclk: Hertz, wdgtb: u8) -> u64 {
+    4096 * (1_u64 << wdgtb) * 1_000_000_000 / u64::from(pclk.0)
+}
+
+/// Computes the prescaler, counter and window values of the WWDG
+fn wwdg_timing(pclk: Hertz, period: MicroSeconds, window: MicroSeconds) -> (u8, u8, u8) {
+    let period_ns = u64::from(period.0) * 1_000;
+
+    // Find the smallest prescaler the period fits into
+    let mut wdgtb = 0;
+    while wdgtb < 3 && period_ns > 64 * wwdg_tick_ns(pclk, wdgtb) {
+        wdgtb += 1;
+    }
+    let tick_ns = wwdg_tick_ns(pclk, wdgtb);
+
+    // The reset happens when the counter rolls over from 0x40 to 0x3F
+    let ticks = ((period_ns + tick_ns - 1) / tick_ns).clamp(1, 64) as u8;
+    let counter = 0x3F + ticks;
+
+    // Feeding is only allowed once the counter is below the window value
+    let window_ticks = (u64::from(window.0) * 1_000 / tick_ns).min(63) as u8;
+    let window = (counter - window_ticks).max(0x40);
+
+    (wdgtb, counter, window)
+}
+
+impl ClockDependent for WindowWatchdog {
+    /// A running watchdog counts with the new PCLK until the next `start`
+    fn update_clocks(&mut self, clocks: &Clocks) {
+        self.pclk = clocks.pclk();
+    }
+}
+
+impl watchdog::Watchdog for WindowWatchdog {
+    /// Feed the watchdog, so that at least one `period` goes by before the next
+    /// reset
+    fn feed(&mut self) {
+        self.wwdg.cr.write(|w| unsafe { w.t().bits(self.counter) });
+    }
+}
+
+impl watchdog::WatchdogEnable for WindowWatchdog {
+    type Time = MicroSeconds;
+
+    /// Starts the watchdog with the given `period`
+    ///
+    /// The period is rounded up to the next counter tick. Periods exceeding the
+    /// range of the WWDG at the current PCLK are clamped to the maximum.
+    fn start<T>(&mut self, period: T)
+    where
+        T: Into<MicroSeconds>,
+    {
+        let (wdgtb, counter, window) = wwdg_timing(self.pclk, period.into(), self.window);
+        self.counter = counter;
+
+        self.wwdg
+            .cfr
+            .modify(|_, w| unsafe { w.wdgtb().bits(wdgtb).w().bits(window) });
+        self.wwdg
+            .cr
+            .write(|w| unsafe { w.t().bits(self.counter).wdga().set_bit() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iwdg_timeout_nominal_lsi() {
+        let timeout = IwdgTimeout::from_ms(MilliSeconds(1_000), LSI_FREQ);
+        assert_eq!((timeout.psc, timeout.reload), (1, 4095));
+        assert_eq!(timeout.to_ms(LSI_FREQ).0, 1_000);
+    }
+
+    #[test]
+    fn iwdg_timeout_calibrated_lsi() {
+        let lsi = Hertz(40_000);
+        let timeout = IwdgTimeout::from_ms(MilliSeconds(1_000), lsi);
+        assert_eq!((timeout.psc, timeout.reload), (2, 2499));
+        assert_eq!(timeout.to_ms(lsi).0, 1_000);
+    }
+
+    #[test]
+    fn iwdg_timeout_rounds_down() {
+        for &ms in &[1, 5, 10, 100, 250, 1_000, 4_321, 10_000, 32_000] {
+            let timeout = IwdgTimeout::from_ms(MilliSeconds(ms), LSI_FREQ);
+            let step = prescaler(timeout.psc) * 1_000 / LSI_FREQ.0 + 1;
+            let reached = timeout.to_ms(LSI_FREQ).0;
+            assert!(reached <= ms, "{} ms", ms);
+            assert!(ms - reached <= step, "{} ms", ms);
+        }
+    }
+
+    #[test]
+    fn iwdg_timeout_clamped() {
+        let timeout = IwdgTimeout::from_ms(MilliSeconds(60_000), LSI_FREQ);
+        assert_eq!((timeout.psc, timeout.reload), (6, 0xFFF));
+        assert_eq!(timeout.to_ms(LSI_FREQ).0, 32_000);
+
+        let timeout = IwdgTimeout::from_ms(MilliSeconds(0), LSI_FREQ);
+        assert_eq!((timeout.psc, timeout.reload), (0, 0));
+    }
+
+    #[test]
+    fn wwdg_timing_selects_smallest_prescaler() {
+        let pclk = Hertz(24_000_000);
+        // One tick lasts 170.7 us without prescaler, 10.9 ms fit into 64 ticks
+        assert_eq!(
+            wwdg_timing(pclk, MicroSeconds(10_000), MicroSeconds(0)),
+            (0, 0x7A, 0x7A)
+        );
+        assert_eq!(
+            wwdg_timing(pclk, MicroSeconds(40_000), MicroSeconds(10_000)),
+            (2, 0x7A, 0x6C)
+        );
+    }
+
+    #[test]
+    fn wwdg_timing_rounds_period_up() {
+        for &pclk in &[1_000_000, 8_000_000, 24_000_000, 48_000_000] {
+            let pclk = Hertz(pclk);
+            for &us in &[100, 1_000, 5_000, 10_000, 20_000, 43_000] {
+                let (wdgtb, counter, _) = wwdg_timing(pclk, MicroSeconds(us), MicroSeconds(0));
+                let tick_ns = wwdg_tick_ns(pclk, wdgtb);
+                let reached_ns = u64::from(counter - 0x3F) * tick_ns;
+                let period_ns = u64::from(us) * 1_000;
+                if counter < 0x7F {
+                    assert!(reached_ns >= period_ns, "{} us at {} Hz", us, pclk.0);
+                    assert!(
+                        reached_ns - period_ns < tick_ns,
+                        "{} us at {} Hz",
+                        us,
+                        pclk.0
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wwdg_timing_clamped() {
+        let pclk = Hertz(24_000_000);
+        assert_eq!(
+            wwdg_timing(pclk, MicroSeconds(1_000_000), MicroSeconds(0)),
+            (3, 0x7F, 0x7F)
+        );
+        assert_eq!(
+            wwdg_timing(pclk, MicroSeconds(0), MicroSeconds(0)),
+            (0, 0x40, 0x40)
+        );
+        // The window can't start before the counter is loaded
+        assert_eq!(
+            wwdg_timing(pclk, MicroSeconds(10_000), MicroSeconds(20_000)),
+            (0, 0x7A, 0x40)
+        );
+    }
+}